@@ -0,0 +1,42 @@
+//! Merging the records of many files at once against folding them in one by one.
+//! Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// the crate has no library target, so the module is compiled in here
+#[allow(dead_code, unused_imports)]
+#[path = "../src"]
+mod src {
+    pub mod mergeline;
+}
+
+use src::mergeline::{merge, merge_all, Line};
+
+// `src::mergeline` only declares it for its unit tests
+#[allow(clippy::duplicate_mod)]
+#[path = "../src/mergeline/fixtures.rs"]
+mod fixtures;
+use fixtures::generate_files;
+
+fn pairwise(files: &[Vec<Line>]) -> Vec<Line> {
+    files
+        .iter()
+        .fold(Vec::new(), |all_lines, file| merge(&all_lines, file))
+}
+
+fn bench_merge(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merge");
+    for count in [2, 10, 50] {
+        let files = generate_files(count, 200_000 / count, 20);
+        group.bench_with_input(BenchmarkId::new("pairwise", count), &files, |b, files| {
+            b.iter(|| pairwise(files))
+        });
+        group.bench_with_input(BenchmarkId::new("k-way", count), &files, |b, files| {
+            b.iter(|| merge_all(files))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_merge);
+criterion_main!(benches);
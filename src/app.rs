@@ -1,28 +1,44 @@
+#[cfg(test)]
+mod test;
+
 use log::*;
 use std::{
-    cmp::min,
-    collections::{HashSet, VecDeque},
+    cmp::{max, min},
+    collections::{HashMap, HashSet, VecDeque},
     io,
-    path::Path,
-    str::FromStr,
+    path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
 
-use futures::executor::block_on;
+use crossterm::event::KeyCode;
+use regex::Regex;
 use tui::{
     backend::{Backend, CrosstermBackend},
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Spans},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs, Wrap},
+    text::{Span, Spans, Text},
+    widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Tabs, Wrap},
     Frame, Terminal,
 };
 
-use crate::timestamp::LineError;
+use crate::cache::IndexCache;
+use crate::decompress::{self, Member};
+use crate::export::{export_to_file, EXPORT_FILENAME};
+use crate::filter::{next_min_level, Filter};
+use crate::follow::Follower;
+use crate::load::{Listing, Loader};
+use crate::scan::{file_rows, scan_directory, FileEntry, FileRow, ScanOptions};
+use crate::timestamp::{
+    format_skew, format_timestamp, parse_skew, parse_timezone, parse_user_time, LineError,
+    TimeCorrection, TimestampParser,
+};
 
-use super::mergeline::merge;
-use super::mergeline::Line;
-use super::text::FileWithLines;
+use super::mergeline::{merge, Line};
+use super::text::{
+    distinct_names, modified_time, peek_span, short_filename, unique_names, FileWithLines,
+    LoadProgress,
+};
 
 // solarized: https://ethanschoonover.com/solarized/
 const FG_COLOR: Color = Color::Rgb(147, 161, 161);
@@ -32,34 +48,46 @@ const BG_ACCENT_COLOR: Color = Color::Rgb(7, 54, 66);
 const ERROR_RED_COLOR: Color = Color::Rgb(220, 50, 47);
 const WARN_YELLOW_COLOR: Color = Color::Rgb(181, 137, 0);
 
-#[derive(Clone)]
-struct FileEntry {
-    filename: String,
-    file_size: u64,
-}
+// solarized accents, one per source file in the merged view
+const SOURCE_COLORS: [Color; 8] = [
+    Color::Rgb(38, 139, 210),
+    Color::Rgb(42, 161, 152),
+    Color::Rgb(133, 153, 0),
+    Color::Rgb(108, 113, 196),
+    Color::Rgb(211, 54, 130),
+    Color::Rgb(203, 75, 22),
+    Color::Rgb(181, 137, 0),
+    Color::Rgb(220, 50, 47),
+];
+const SOURCE_MARKER: &str = "▌";
+/// Row of an expanded archive while its members are listed.
+const LISTING: &str = "listing…";
+
+/// `+` and `-` in the sources dialog change the skew of a file by this many microseconds.
+const SKEW_STEP: i64 = 100_000;
 
-impl Ord for FileEntry {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.filename.cmp(&other.filename)
-    }
+#[derive(Clone)]
+struct Item {
+    text: String,
+    /// Index into `ViewMenu::files` of the file the record comes from, `None` for anything else.
+    source_file: Option<usize>,
 }
 
-impl PartialOrd for FileEntry {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl Item {
+    fn plain(text: String) -> Item {
+        Item {
+            text,
+            source_file: None,
+        }
     }
-}
 
-impl PartialEq for FileEntry {
-    fn eq(&self, other: &Self) -> bool {
-        self.filename == other.filename
+    fn filler() -> Item {
+        Item::plain("~".to_string())
     }
 }
 
-impl Eq for FileEntry {}
-
 struct Common {
-    items: VecDeque<String>,
+    items: VecDeque<Item>,
     state: ListState,
     absolute_index: usize,
 }
@@ -67,7 +95,7 @@ struct Common {
 impl Common {
     fn new(it: Vec<String>) -> Common {
         Common {
-            items: it.into(),
+            items: it.into_iter().map(Item::plain).collect(),
             state: ListState::default(),
             absolute_index: 0,
         }
@@ -86,129 +114,715 @@ impl FileListMenu {
     }
 }
 
+struct Search {
+    regex: Regex,
+    reverse: bool,
+    /// Positions in `ViewMenu::visible` of all matching records, in ascending order.
+    matches: Vec<usize>,
+    current: Option<usize>,
+}
+
 struct ViewMenu {
     files: Vec<FileWithLines>,
     all_lines: Vec<Line>,
+    filter: Filter,
+    /// Indices into `all_lines` of the records passing `filter`, paging and search work on these.
+    visible: Vec<usize>,
     details_dialog: Option<String>,
+    search: Option<Search>,
+    /// Selected row of the dialog toggling source files, if open.
+    sources_dialog: Option<usize>,
+    /// Short names of `files`, shown in the legend and the gutter.
+    labels: Vec<String>,
+    show_gutter: bool,
+    /// Watches `files` for new lines while in follow mode.
+    follower: Option<Follower>,
+    /// Latest timestamp of every file, records written later with an earlier one are out of order.
+    latest: Vec<i64>,
+    /// Record with the last lines of every file, lines without a timestamp written later continue it.
+    last_records: Vec<Option<Line>>,
 }
 
 impl ViewMenu {
-    fn new(files: Vec<FileWithLines>) -> Result<ViewMenu, LineError> {
-        let mut res = ViewMenu {
-            files,
-            all_lines: Vec::new(),
+    fn new(files: Vec<FileWithLines>, all_lines: Vec<Line>, filter: Filter) -> ViewMenu {
+        let mut latest: Vec<i64> = vec![i64::MIN; files.len()];
+        let mut last_records: Vec<Option<Line>> = vec![None; files.len()];
+        for line in &all_lines {
+            latest[line.source_file] = line.timestamp;
+            let last = &mut last_records[line.source_file];
+            if last.as_ref().is_none_or(|last| last.index < line.index) {
+                *last = Some(line.clone());
+            }
+        }
+        let res = ViewMenu {
+            visible: filter.apply(&files, &all_lines),
+            all_lines,
+            filter,
             details_dialog: None,
+            search: None,
+            sources_dialog: None,
+            labels: distinct_names(&files.iter().map(|f| f.filename()).collect::<Vec<String>>()),
+            show_gutter: false,
+            follower: None,
+            latest,
+            last_records,
+            files,
         };
 
-        for i in 0..res.files.len() {
-            let file_lines: Vec<Line> = res.files[i].get_annotated_lines(i)?;
-            res.all_lines = merge(&res.all_lines, &file_lines);
-        }
-
         info!(
             "ViewMenu::new - lines merged, total count={}",
             res.all_lines.len()
         );
 
-        Ok(res)
+        res
     }
 
-    fn get_lines(&self, from: usize, to: usize) -> Vec<String> {
-        let mut result: Vec<String> = Vec::new();
+    fn len(&self) -> usize {
+        self.visible.len()
+    }
+
+    fn get_lines(&self, from: usize, to: usize) -> Vec<Item> {
+        let mut result: Vec<Item> = Vec::new();
 
-        let from = min(from, self.all_lines.len());
-        let to = min(to, self.all_lines.len());
+        let from = min(from, self.len());
+        let to = min(to, self.len());
 
-        if to <= from || from > self.all_lines.len() - 1 {
+        if to <= from || from > self.len() - 1 {
             return Vec::new();
         }
 
         for i in from..to {
-            let line = &self.all_lines[i];
-            result.push(
-                self.files[line.source_file]
-                    .get_ith_line(line.index)
-                    .unwrap()
-                    .to_string(),
-            );
+            let line = &self.all_lines[self.visible[i]];
+            // e.g. the file was truncated since it was loaded
+            let text = match self.files[line.source_file].get_record(line) {
+                Ok(record) => record.to_string(),
+                Err(err) => format!("<cannot read record: {}>", err),
+            };
+            result.push(Item {
+                text,
+                source_file: Some(line.source_file),
+            });
         }
 
         result
     }
+
+    fn source_color(source_file: usize) -> Color {
+        SOURCE_COLORS[source_file % SOURCE_COLORS.len()]
+    }
+
+    /// The colored marker and, if enabled, the short name of the source file put in front of every line of a record.
+    fn gutter(&self, source_file: usize) -> (Span<'_>, Span<'static>) {
+        let style = Style::default().fg(ViewMenu::source_color(source_file));
+        let width = match self.show_gutter {
+            true => {
+                self.labels
+                    .iter()
+                    .map(|l| l.chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    + 1
+            }
+            false => 0,
+        };
+        let label = match self.show_gutter {
+            true => Span::styled(
+                format!("{:<width$}", self.labels[source_file], width = width),
+                style,
+            ),
+            false => Span::raw(""),
+        };
+        (label, Span::styled(SOURCE_MARKER, style))
+    }
+
+    /// Describes every loaded file, shown by `i`.
+    fn file_info(&self, parser: &TimestampParser) -> String {
+        let mut records: Vec<usize> = vec![0; self.files.len()];
+        let mut first: Vec<Option<i64>> = vec![None; self.files.len()];
+        let mut last: Vec<Option<i64>> = vec![None; self.files.len()];
+        for line in &self.all_lines {
+            records[line.source_file] += 1;
+            first[line.source_file].get_or_insert(line.timestamp);
+            last[line.source_file] = Some(line.timestamp);
+        }
+
+        let time = |t: Option<i64>| t.map(format_timestamp).unwrap_or_default();
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| {
+                format!(
+                    "{}\n  lines={} records={} out of order={}\n  format={} correction={}\n  first={} last={}\n",
+                    file.filename(),
+                    file.len(),
+                    records[i],
+                    file.out_of_order(),
+                    file.detected_format().unwrap_or_default(),
+                    file.time_correction(parser),
+                    time(first[i]),
+                    time(last[i]),
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn legend(&self) -> Spans<'_> {
+        let mut spans: Vec<Span> = Vec::new();
+        for (i, label) in self.labels.iter().enumerate() {
+            let style = Style::default().fg(ViewMenu::source_color(i));
+            spans.push(Span::styled(SOURCE_MARKER, style));
+            spans.push(Span::styled(label.as_str(), style));
+            spans.push(Span::raw("  "));
+        }
+        Spans::from(spans)
+    }
+
+    fn visible_lines(&self) -> Vec<Line> {
+        self.visible
+            .iter()
+            .map(|i| self.all_lines[*i].clone())
+            .collect()
+    }
+
+    /// Positions in `visible` of the records matching `regex`, starting at `from`.
+    fn find_matches(&self, regex: &Regex, from: usize) -> Vec<usize> {
+        self.visible
+            .iter()
+            .enumerate()
+            .skip(from)
+            .filter(|(_, i)| {
+                let line = &self.all_lines[**i];
+                match self.files[line.source_file].get_record(line) {
+                    Ok(record) => regex.is_match(&record),
+                    Err(_) => false,
+                }
+            })
+            .map(|(position, _)| position)
+            .collect()
+    }
+
+    /// Recomputes the visible records, returns the new position of the record at `position`, or of the one after it.
+    fn refilter(&mut self, position: usize) -> usize {
+        let anchor = self.visible.get(position).copied();
+        self.refilter_at(anchor)
+    }
+
+    /// Same as `refilter`, with the record to stay on given as an index into `all_lines`.
+    fn refilter_at(&mut self, anchor: Option<usize>) -> usize {
+        self.visible = self.filter.apply(&self.files, &self.all_lines);
+        let matches = self.search.as_ref().map(|s| self.find_matches(&s.regex, 0));
+        if let (Some(search), Some(matches)) = (&mut self.search, matches) {
+            search.matches = matches;
+            search.current = None;
+        }
+
+        info!("ViewMenu::refilter - visible count={}", self.visible.len());
+
+        match anchor {
+            Some(anchor) => min(
+                self.visible.partition_point(|i| *i < anchor),
+                self.len().saturating_sub(1),
+            ),
+            None => 0,
+        }
+    }
+
+    /// Reads the timestamps of `source_file` again with its current correction and merges them back in.
+    /// Returns the new position of the record at `position`.
+    fn remerge_file(
+        &mut self,
+        source_file: usize,
+        parser: &TimestampParser,
+        position: usize,
+    ) -> Result<usize, LineError> {
+        let (file_lines, out_of_order) = self.files[source_file]
+            .get_annotated_lines_with_progress(
+                source_file,
+                parser,
+                &LoadProgress::default(),
+                None,
+            )?;
+        self.files[source_file].set_out_of_order(out_of_order);
+        self.latest[source_file] = file_lines.last().map_or(i64::MIN, |l| l.timestamp);
+        self.last_records[source_file] = file_lines.iter().max_by_key(|l| l.index).cloned();
+        let anchor = self
+            .visible
+            .get(position)
+            .map(|i| (self.all_lines[*i].source_file, self.all_lines[*i].index));
+
+        self.all_lines.retain(|l| l.source_file != source_file);
+        self.all_lines = merge(&self.all_lines, &file_lines);
+        info!(
+            "ViewMenu::remerge_file - file={}, correction={}",
+            self.files[source_file].filename(),
+            self.files[source_file].time_correction(parser)
+        );
+
+        let anchor = anchor.and_then(|(source_file, index)| {
+            self.all_lines
+                .iter()
+                .position(|l| l.source_file == source_file && l.index == index)
+        });
+        Ok(self.refilter_at(anchor))
+    }
+
+    /// Removes the records of `source_file`, returns the new position of the record at `position`.
+    fn drop_records(&mut self, source_file: usize, position: usize) -> usize {
+        let anchor = self
+            .visible
+            .get(position)
+            .map(|i| self.all_lines[*i].clone());
+        self.all_lines.retain(|l| l.source_file != source_file);
+        self.latest[source_file] = i64::MIN;
+        self.last_records[source_file] = None;
+        let anchor = anchor.map(|line| self.all_lines.partition_point(|l| *l < line));
+        self.refilter_at(anchor)
+    }
+
+    /// Reads what was written to the followed files and merges the new records in.
+    /// Returns the new position of the record at `position`, `None` if nothing changed.
+    fn follow(
+        &mut self,
+        parser: &TimestampParser,
+        mut position: usize,
+    ) -> Result<Option<usize>, LineError> {
+        let Some(follower) = &mut self.follower else {
+            return Ok(None);
+        };
+        let written = follower.poll();
+        let mut dropped = false;
+        for source_file in follower.take_truncated() {
+            // the lines of a truncated file kept on disk would be read from other text now
+            if self.files[source_file].drop_on_disk() {
+                position = self.drop_records(source_file, position);
+                dropped = true;
+            }
+        }
+        if written.is_empty() {
+            return Ok(dropped.then_some(position));
+        }
+
+        let mut new_lines: Vec<Line> = Vec::new();
+        for (source_file, text) in written {
+            let file = &mut self.files[source_file];
+            let appended = file.append(&text);
+            // lines before the first record of a file, e.g. one read again after truncation, wait for it
+            let from = match self.last_records[source_file] {
+                Some(_) => appended,
+                None => 0,
+            };
+            let (file_lines, continued, out_of_order) =
+                file.get_appended_lines(source_file, parser, from, self.latest[source_file])?;
+            file.set_out_of_order(file.out_of_order() + out_of_order);
+            if let Some(last) = file_lines.last() {
+                self.latest[source_file] = max(self.latest[source_file], last.timestamp);
+            }
+            // `all_lines` is sorted, the record is found without going through all of them
+            if continued > 0 {
+                if let Some(last) = &mut self.last_records[source_file] {
+                    if let Ok(i) = self.all_lines.binary_search(last) {
+                        self.all_lines[i].line_count += continued;
+                    }
+                    last.line_count += continued;
+                }
+            }
+            if let Some(last) = file_lines.iter().max_by_key(|l| l.index) {
+                self.last_records[source_file] = Some(last.clone());
+            }
+            new_lines = merge(&new_lines, &file_lines);
+        }
+        info!("ViewMenu::follow - new records, count={}", new_lines.len());
+
+        // usually all new records are the latest, then the positions of the others stay the same
+        let latest = match (self.all_lines.last(), new_lines.first()) {
+            (Some(last), Some(first)) => last < first,
+            _ => true,
+        };
+        if !latest {
+            let anchor = self
+                .visible
+                .get(position)
+                .map(|i| self.all_lines[*i].clone());
+            self.all_lines = merge(&self.all_lines, &new_lines);
+            let anchor = anchor.map(|line| self.all_lines.partition_point(|l| *l < line));
+            return Ok(Some(self.refilter_at(anchor)));
+        }
+
+        let start = self.all_lines.len();
+        let first_visible = self.visible.len();
+        self.all_lines.extend(new_lines);
+        let visible = self.filter.apply(&self.files, &self.all_lines[start..]);
+        self.visible.extend(visible.into_iter().map(|i| i + start));
+        let matches = self
+            .search
+            .as_ref()
+            .map(|s| self.find_matches(&s.regex, first_visible));
+        if let (Some(search), Some(matches)) = (&mut self.search, matches) {
+            search.matches.extend(matches);
+        }
+        Ok(Some(position))
+    }
 }
 
-enum AppState {
-    FileList(FileListMenu),
-    TextView(ViewMenu),
+enum PromptKind {
+    Search {
+        reverse: bool,
+    },
+    Include,
+    Exclude,
+    GoToTime,
+    From,
+    To,
+    Timezone,
+    Skew,
+    /// Where to export the records to.
+    Export {
+        prefix_filename: bool,
+        filtered: bool,
+    },
+    /// Whether to overwrite the existing file at `path` with the export.
+    Overwrite {
+        path: PathBuf,
+        prefix_filename: bool,
+        filtered: bool,
+    },
+}
+
+struct Prompt {
+    kind: PromptKind,
+    text: String,
 }
 
-struct AppError {
-    error_message: String,
+impl Prompt {
+    fn prefix(&self) -> String {
+        match &self.kind {
+            PromptKind::Search { reverse: false } => String::from("/"),
+            PromptKind::Search { reverse: true } => String::from("?"),
+            PromptKind::Include => String::from("include: "),
+            PromptKind::Exclude => String::from("exclude: "),
+            PromptKind::GoToTime => String::from("go to time: "),
+            PromptKind::From => String::from("from time: "),
+            PromptKind::To => String::from("to time: "),
+            PromptKind::Timezone => String::from("timezone: "),
+            PromptKind::Skew => String::from("skew in seconds: "),
+            PromptKind::Export { .. } => String::from("export to: "),
+            PromptKind::Overwrite { path, .. } => {
+                format!("{} exists, overwrite? (y/n): ", path.display())
+            }
+        }
+    }
+}
+
+enum AppState {
+    FileList(FileListMenu),
+    TextView(Box<ViewMenu>),
 }
 
 struct App {
     common: Common,
     app_state: AppState,
     file_list: Vec<FileEntry>,
+    /// `file_list` as a tree, one row per item of the file list.
+    file_rows: Vec<FileRow>,
+    /// Directories of the file list showing only their own row.
+    collapsed_dirs: HashSet<String>,
+    /// Rotation families, by path, and archives, by filename, of the file list showing their members.
+    expanded: HashSet<String>,
+    /// First and last timestamp of files loaded now or before, by filename.
+    time_spans: HashMap<String, (i64, i64)>,
+    directory: std::path::PathBuf,
     terminal_size: tui::layout::Rect,
     error: Option<String>,
+    /// Shown in the status line until the next key, e.g. where the records were exported to.
+    message: Option<String>,
+    prompt: Option<Prompt>,
+    /// Filter given on the command line, every newly loaded view starts with it.
+    initial_filter: Filter,
+    parser: Arc<TimestampParser>,
+    /// Files being loaded in the background, the file list stays until they are merged.
+    loader: Option<Loader>,
+    /// Archives expanded before their members were listed, listed in the background.
+    listings: Vec<Listing>,
+    /// Keeps the line breaks and timestamps of loaded files for the next time.
+    cache: Option<Arc<IndexCache>>,
+    /// Timestamp format of every file loaded so far, by filename.
+    detected_formats: HashMap<String, String>,
 }
 
 impl App {
-    fn new(path: &std::path::Path, size: tui::layout::Rect) -> Result<App, std::io::Error> {
+    fn new(
+        path: &std::path::Path,
+        size: tui::layout::Rect,
+        initial_filter: Filter,
+        parser: TimestampParser,
+        cache: Option<IndexCache>,
+        scan: ScanOptions,
+    ) -> Result<App, std::io::Error> {
         info!("App::new - new App");
-        let file_list = App::scan_directory(path)?;
+        let file_list = scan_directory(path, &scan)?;
         let mut app = App {
-            common: Common::new(file_list.iter().map(|f| f.filename.clone()).collect()),
+            common: Common::new(Vec::new()),
             app_state: AppState::FileList(FileListMenu::new()),
             file_list,
+            file_rows: Vec::new(),
+            collapsed_dirs: HashSet::new(),
+            expanded: HashSet::new(),
+            time_spans: HashMap::new(),
+            directory: path.to_path_buf(),
             terminal_size: size,
             error: None,
+            message: None,
+            prompt: None,
+            initial_filter,
+            parser: Arc::new(parser),
+            loader: None,
+            listings: Vec::new(),
+            cache: cache.map(Arc::new),
+            detected_formats: HashMap::new(),
         };
-
-        if !app.common.items.is_empty() {
-            app.common.state.select(Some(0));
-        }
+        app.show_file_rows(0);
+        app.find_time_spans();
 
         Ok(app)
     }
 
-    fn scan_directory(path: &std::path::Path) -> Result<Vec<FileEntry>, std::io::Error> {
-        let mut result: Vec<FileEntry> = Vec::new();
-        for item in std::fs::read_dir(path)? {
-            let item_path = item?.path();
-            if !item_path.is_file() || item_path.file_name().is_none() {
+    /// Lists the rows of the file tree, selecting the one at `selected`, or the last one.
+    fn show_file_rows(&mut self, selected: usize) {
+        self.file_rows = file_rows(&self.file_list, &self.collapsed_dirs, &self.expanded);
+        self.common.items = self
+            .file_rows
+            .iter()
+            .map(|row| match row {
+                FileRow::Dir { path, .. } | FileRow::Family { path, .. } => {
+                    Item::plain(path.clone())
+                }
+                FileRow::File { entry, .. }
+                | FileRow::Archive { entry, .. }
+                | FileRow::Listing { entry, .. } => {
+                    Item::plain(self.file_list[*entry].filename.clone())
+                }
+                FileRow::Member { entry, member, .. } => {
+                    Item::plain(member_of(&self.file_list, *entry, *member).filename.clone())
+                }
+            })
+            .collect();
+        self.common.state = ListState::default();
+        if !self.common.items.is_empty() {
+            self.common
+                .state
+                .select(Some(min(selected, self.common.items.len() - 1)));
+        }
+    }
+
+    /// Time spans of the rotated files not loaded yet, from the index cache, or the head and tail of plain files.
+    /// Compressed files not in the cache have none until they are loaded.
+    fn find_time_spans(&mut self) {
+        for row in &self.file_rows {
+            let FileRow::Family { entries, .. } = row else {
                 continue;
+            };
+            for entry in entries {
+                let filename = &self.file_list[*entry].filename;
+                let cached = self.cache.as_ref().and_then(|cache| {
+                    cache.span(
+                        filename,
+                        self.parser.fingerprint(filename, modified_time(filename)),
+                    )
+                });
+                let span = match cached {
+                    Some(span) => Some(span),
+                    None => peek_span(filename, &self.parser).unwrap_or_else(|err| {
+                        warn!(
+                            "App::find_time_spans - cannot read file={}, error={}",
+                            filename, err
+                        );
+                        None
+                    }),
+                };
+                if let Some(span) = span {
+                    self.time_spans.insert(filename.clone(), span);
+                }
             }
-            let metadata = std::fs::metadata(item_path.clone())?;
-            match item_path.file_name().unwrap().to_os_string().into_string() {
-                Ok(file) => {
-                    if file.as_bytes()[0] != b'.' {
-                        let fullpath = path
-                            .join(std::path::Path::new(&file))
-                            .canonicalize()
-                            .unwrap();
-
-                        if metadata.len() == 0 {
-                            continue;
+        }
+    }
+
+    /// Shows the members of the archives listed in the background, a failure shows in the error popup.
+    fn poll_listings(&mut self) {
+        let mut listed: Vec<(String, io::Result<Vec<Member>>)> = Vec::new();
+        self.listings
+            .retain_mut(|listing| match listing.try_result() {
+                Some(result) => {
+                    listed.push((listing.archive.clone(), result));
+                    false
+                }
+                None => true,
+            });
+        if listed.is_empty() {
+            return;
+        }
+        for (archive, result) in listed {
+            let Some(entry) = self.file_list.iter().position(|e| e.filename == archive) else {
+                continue;
+            };
+            match result {
+                Ok(members) => {
+                    self.file_list[entry].set_members(&members);
+                    let members = self.loadable(&[entry]);
+                    if let AppState::FileList(file_list) = &mut self.app_state {
+                        // the archive stood for all of its members, which are selected one by one now
+                        if file_list.loaded_items.remove(&archive) {
+                            file_list.loaded_items.extend(members);
                         }
-                        result.push(FileEntry {
-                            filename: fullpath.as_os_str().to_os_string().into_string().unwrap(),
-                            file_size: metadata.len(),
-                        });
                     }
                 }
-                Err(err_file) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("filename is not Unicode, filename={:?}", err_file),
-                    ))
+                Err(err) => {
+                    self.expanded.remove(&archive);
+                    self.error = Some(format!(
+                        "App::poll_listings - cannot list archive={}, error={}",
+                        archive, err
+                    ));
                 }
             }
         }
+        // the log view shows the new rows once it goes back to the file list
+        if !matches!(self.app_state, AppState::FileList(_)) {
+            return;
+        }
+        // the cursor stays on its row, on the first member instead of the listing row
+        let current = self
+            .common
+            .state
+            .selected()
+            .and_then(|i| self.file_rows.get(i).cloned());
+        let selected = self.common.state.selected().unwrap_or_default();
+        self.show_file_rows(selected);
+        let row = self.file_rows.iter().position(|row| match (row, &current) {
+            (FileRow::Member { entry, member, .. }, Some(FileRow::Listing { entry: e, .. })) => {
+                entry == e && *member == 0
+            }
+            (row, Some(current)) => row == current,
+            _ => false,
+        });
+        self.common
+            .state
+            .select(row.or(self.common.state.selected()));
+    }
 
-        result.sort();
-        Ok(result)
+    /// Paths to load for the scanned files `entries`, the members of archives once they are listed.
+    /// Archives not listed yet stand for all of their members, which are listed when loading them.
+    fn loadable(&self, entries: &[usize]) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for entry in entries {
+            let entry = &self.file_list[*entry];
+            match &entry.members {
+                Some(members) if entry.archive => {
+                    files.extend(members.iter().map(|m| m.filename.clone()))
+                }
+                _ => files.push(entry.filename.clone()),
+            }
+        }
+        files
+    }
+
+    /// The expanded rotation family listing `entry` as a member.
+    fn family_of(&self, entry: usize) -> Option<String> {
+        self.file_rows.iter().find_map(|row| match row {
+            FileRow::Family { path, entries, .. } if entries.contains(&entry) => Some(path.clone()),
+            _ => None,
+        })
+    }
+
+    /// Collapses or expands the directory, rotation family or archive of the file list under the cursor.
+    /// Collapsing a file collapses the archive, family or else the directory holding it.
+    fn collapse(&mut self, collapse: bool) {
+        if !matches!(self.app_state, AppState::FileList(_)) {
+            return;
+        }
+        let Some(selected) = self.common.state.selected() else {
+            return;
+        };
+        let archive = match &self.file_rows[selected] {
+            FileRow::Archive {
+                entry, expanded, ..
+            } if *expanded == collapse => Some(*entry),
+            FileRow::Member { entry, .. } | FileRow::Listing { entry, .. } if collapse => {
+                Some(*entry)
+            }
+            _ => None,
+        };
+        if let Some(entry) = archive {
+            let filename = self.file_list[entry].filename.clone();
+            // members are only listed once the archive is expanded the first time, in the background
+            let listing = self.listings.iter().any(|l| l.archive == filename);
+            if !collapse && self.file_list[entry].members.is_none() && !listing {
+                self.listings.push(Listing::start(filename.clone()));
+            }
+            match collapse {
+                true => self.expanded.remove(&filename),
+                false => self.expanded.insert(filename.clone()),
+            };
+            info!(
+                "App::collapse - archive={}, expanded={}",
+                filename, !collapse
+            );
+            self.show_file_rows(selected);
+            // the cursor stays on the archive
+            let row = self
+                .file_rows
+                .iter()
+                .position(|row| matches!(row, FileRow::Archive { entry: e, .. } if *e == entry));
+            self.common.state.select(row.or(Some(selected)));
+            return;
+        }
+
+        let family = match &self.file_rows[selected] {
+            FileRow::Family { path, expanded, .. } if *expanded == collapse => Some(path.clone()),
+            FileRow::File { entry, .. } | FileRow::Archive { entry, .. } if collapse => {
+                self.family_of(*entry)
+            }
+            _ => None,
+        };
+        if let Some(family) = family {
+            match collapse {
+                true => self.expanded.remove(&family),
+                false => self.expanded.insert(family.clone()),
+            };
+            info!("App::collapse - family={}, expanded={}", family, !collapse);
+            self.show_file_rows(selected);
+            // the cursor stays on the family
+            let row = self
+                .file_rows
+                .iter()
+                .position(|row| matches!(row, FileRow::Family { path, .. } if *path == family));
+            self.common.state.select(row.or(Some(selected)));
+            return;
+        }
+
+        let dir = match &self.file_rows[selected] {
+            FileRow::Dir { path, .. } => path.clone(),
+            FileRow::File { entry, .. } | FileRow::Archive { entry, .. } if collapse => {
+                self.file_list[*entry].dir.clone()
+            }
+            FileRow::Family { entries, .. } if collapse => self.file_list[entries[0]].dir.clone(),
+            _ => return,
+        };
+        if dir.is_empty() {
+            return;
+        }
+        match collapse {
+            true => self.collapsed_dirs.insert(dir.clone()),
+            false => self.collapsed_dirs.remove(&dir),
+        };
+        info!("App::collapse - dir={}, collapsed={}", dir, collapse);
+
+        self.show_file_rows(selected);
+        // the cursor stays on the directory
+        let row = self
+            .file_rows
+            .iter()
+            .position(|row| matches!(row, FileRow::Dir { path, .. } if *path == dir));
+        self.common.state.select(row.or(Some(selected)));
     }
 
     fn select_next(&mut self) {
@@ -278,107 +892,128 @@ impl App {
     }
 
     fn flip_current(&mut self) {
-        match &mut self.app_state {
-            AppState::FileList(file_list) => {
-                if let Some(selected) = self.common.state.selected() {
-                    if file_list
-                        .loaded_items
-                        .contains(&App::to_abs_path(&self.common.items[selected]))
-                    {
-                        file_list
-                            .loaded_items
-                            .remove(&App::to_abs_path(&self.common.items[selected]));
-                    } else {
-                        assert!(selected < self.common.items.len());
-                        file_list
-                            .loaded_items
-                            .insert(App::to_abs_path(&self.common.items[selected]));
-                    }
-                }
+        if !matches!(self.app_state, AppState::FileList(_)) {
+            return;
+        }
+        let Some(selected) = self.common.state.selected() else {
+            return;
+        };
+        // selects every file below the directory, of the family or in the archive, or none if all of them were
+        let entries: Vec<usize> = match &self.file_rows[selected] {
+            FileRow::File { entry, .. }
+            | FileRow::Archive { entry, .. }
+            | FileRow::Listing { entry, .. } => vec![*entry],
+            FileRow::Member { .. } => Vec::new(),
+            FileRow::Dir { path, .. } => {
+                let prefix = format!("{}/", path);
+                (0..self.file_list.len())
+                    .filter(|i| {
+                        let dir = &self.file_list[*i].dir;
+                        dir == path || dir.starts_with(&prefix)
+                    })
+                    .collect()
             }
-            AppState::TextView(_) => {}
+            FileRow::Family { entries, .. } => entries.clone(),
+        };
+        let files = match self.file_rows[selected] {
+            FileRow::Member { entry, member, .. } => {
+                vec![member_of(&self.file_list, entry, member).filename.clone()]
+            }
+            _ => self.loadable(&entries),
+        };
+        if let AppState::FileList(file_list) = &mut self.app_state {
+            flip_all(&mut file_list.loaded_items, files);
         }
     }
 
-    fn to_abs_path(path: &String) -> String {
-        std::fs::canonicalize(path)
-            .unwrap()
-            .as_os_str()
-            .to_os_string()
-            .into_string()
-            .unwrap()
-    }
-
     fn enter(&mut self) {
-        let result_new_state: Result<Option<AppState>, AppError> = match &mut self.app_state {
+        match &mut self.app_state {
             AppState::TextView(view) => App::show_details_dialog(
                 view,
-                self.common.items[self.common.state.selected().unwrap()].clone(),
+                self.common.items[self.common.state.selected().unwrap()]
+                    .text
+                    .clone(),
             ),
             AppState::FileList(file_list) => {
-                if file_list.loaded_items.is_empty() {
-                    return;
-                } else {
-                    App::load_files(file_list)
+                if !file_list.loaded_items.is_empty() && self.loader.is_none() {
+                    self.loader = Some(App::load_files(file_list, &self.parser, &self.cache));
                 }
             }
-        };
-
-        match result_new_state {
-            Ok(new_state_opt) => {
-                if let Some(new_state) = new_state_opt {
-                    self.app_state = new_state;
-                    match &self.app_state {
-                        AppState::FileList(_) => {}
-                        AppState::TextView(view) => {
-                            if !view.files.is_empty() {
-                                self.common.items =
-                                    view.get_lines(0, self.terminal_size.height.into()).into();
-                                self.common.state = ListState::default();
-                                self.common.absolute_index = 0;
-                                if !self.common.items.is_empty() {
-                                    self.common.state.select(Some(0));
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            Err(error) => {
-                self.error = Some(error.error_message);
-            }
         }
     }
 
-    fn load_files(file_list: &mut FileListMenu) -> Result<Option<AppState>, AppError> {
+    fn load_files(
+        file_list: &FileListMenu,
+        parser: &Arc<TimestampParser>,
+        cache: &Option<Arc<IndexCache>>,
+    ) -> Loader {
         let mut to_load: Vec<String> = Vec::new();
         for lf in &file_list.loaded_items {
             to_load.push(lf.clone());
         }
+        // the same order as the file list, so equal timestamps always merge the same way
+        to_load.sort();
+
+        info!("App::load_files - loading count={}", to_load.len());
+        Loader::start(to_load, parser.clone(), cache.clone())
+    }
+
+    /// Shows the log view once the files loading in the background are merged.
+    /// Files which could not be loaded are listed in the error popup, the others are shown without them.
+    fn poll_loader(&mut self) {
+        self.poll_listings();
+        let Some(loaded) = self.loader.as_mut().and_then(|loader| loader.try_result()) else {
+            return;
+        };
+        self.loader = None;
+        info!(
+            "App::poll_loader - {} files loaded, {} failed",
+            loaded.files.len(),
+            loaded.failed.len()
+        );
 
-        info!("App::load_files - preparing to load files");
-        let file_futures = Box::pin(FileWithLines::from_files(to_load));
-        let files = block_on(file_futures);
+        if !loaded.failed.is_empty() {
+            let failed: Vec<String> = loaded.failed.iter().map(|err| err.file.clone()).collect();
+            let reasons: Vec<String> = unique_names(&failed)
+                .iter()
+                .zip(&loaded.failed)
+                .map(|(name, err)| format!("{}: {}", name, err.kind))
+                .collect();
+            self.error = Some(format!(
+                "Cannot load {} of {} files\n\n{}",
+                loaded.failed.len(),
+                loaded.failed.len() + loaded.files.len(),
+                reasons.join("\n")
+            ));
+        }
+        if !loaded.files.is_empty() {
+            self.open_view(loaded.files, loaded.lines);
+        }
+    }
 
-        info!("App::load_files - {} files loaded", files.len());
+    /// Shows the merged `lines` of `files` from the start.
+    fn open_view(&mut self, files: Vec<FileWithLines>, lines: Vec<Line>) {
+        let view = ViewMenu::new(files, lines, self.initial_filter.clone());
+        self.common.items = view.get_lines(0, self.terminal_size.height.into()).into();
+        self.common.state = ListState::default();
+        self.common.absolute_index = 0;
+        if !self.common.items.is_empty() {
+            self.common.state.select(Some(0));
+        }
+        self.app_state = AppState::TextView(Box::new(view));
+    }
 
-        match ViewMenu::new(files) {
-            Ok(new_state) => Ok(Some(AppState::TextView(new_state))),
-            Err(err) => Err(AppError {
-                error_message: format!("App::load_files - cannot load files, error={}", err),
-            }),
+    fn cancel_loading(&mut self) {
+        if let Some(loader) = self.loader.take() {
+            loader.cancel();
         }
     }
 
-    fn show_details_dialog(
-        menu: &mut ViewMenu,
-        text: String,
-    ) -> Result<Option<AppState>, AppError> {
+    fn show_details_dialog(menu: &mut ViewMenu, text: String) {
         match menu.details_dialog {
             Some(_) => menu.details_dialog = None,
             None => menu.details_dialog = Some(text),
         }
-        return Ok(None);
     }
 
     fn go_to_file_list(&mut self) {
@@ -386,18 +1021,36 @@ impl App {
             AppState::FileList(_) => {}
             AppState::TextView(view_menu) => {
                 let mut new_file_menu = FileListMenu::new();
-                for file in &view_menu.files {
-                    new_file_menu.loaded_items.insert(file.filename());
+                let mut spans: Vec<Option<(i64, i64)>> = vec![None; view_menu.files.len()];
+                for line in &view_menu.all_lines {
+                    let span =
+                        spans[line.source_file].get_or_insert((line.timestamp, line.timestamp));
+                    span.1 = line.timestamp;
+                }
+                for (file, span) in view_menu.files.iter().zip(spans) {
+                    if let Some(span) = span {
+                        self.time_spans.insert(file.filename(), span);
+                    }
+                    // members of an archive not listed in the file list were loaded by selecting the archive
+                    let filename = file.filename();
+                    let (archive, member) = decompress::split_member(&filename);
+                    let unlisted = member.is_some()
+                        && self
+                            .file_list
+                            .iter()
+                            .any(|e| e.filename == archive && e.members.is_none());
+                    new_file_menu.loaded_items.insert(match unlisted {
+                        true => archive.to_string(),
+                        false => filename.clone(),
+                    });
+                    if let Some(format) = file.detected_format() {
+                        self.detected_formats
+                            .insert(file.filename(), String::from(format));
+                    }
                 }
 
                 self.app_state = AppState::FileList(new_file_menu);
-
-                self.common.items = self.file_list.iter().map(|f| f.filename.clone()).collect();
-                self.common.state = ListState::default();
-
-                if !self.common.items.is_empty() {
-                    self.common.state.select(Some(0));
-                }
+                self.show_file_rows(0);
             }
         }
     }
@@ -406,7 +1059,7 @@ impl App {
         match &mut self.app_state {
             AppState::TextView(view) => {
                 let new_from = min(
-                    view.all_lines.len(),
+                    view.len(),
                     self.common.absolute_index + self.terminal_size.height as usize / 2,
                 );
                 let new_end = new_from + self.terminal_size.height as usize;
@@ -415,7 +1068,7 @@ impl App {
                     return;
                 }
                 while new_items.len() < self.terminal_size.height as usize {
-                    new_items.push("~".to_string());
+                    new_items.push(Item::filler());
                 }
                 self.common.items = new_items.into();
                 self.common.absolute_index = new_from;
@@ -442,7 +1095,7 @@ impl App {
                     return;
                 }
                 while new_items.len() < self.terminal_size.height as usize {
-                    new_items.push("~".to_string());
+                    new_items.push(Item::filler());
                 }
                 self.common.items = new_items.into();
                 self.common.absolute_index = new_from;
@@ -460,7 +1113,7 @@ impl App {
             AppState::TextView(view) => {
                 let mut new_items = view.get_lines(0, self.terminal_size.height as usize);
                 while new_items.len() < self.terminal_size.height as usize {
-                    new_items.push("~".to_string());
+                    new_items.push(Item::filler());
                 }
                 self.common.items = new_items.into();
                 self.common.absolute_index = 0;
@@ -473,20 +1126,523 @@ impl App {
     fn end(&mut self) {
         match &mut self.app_state {
             AppState::TextView(view) => {
-                let new_to: usize = view.all_lines.len();
+                let new_to: usize = view.len();
+                if new_to == 0 {
+                    return;
+                }
                 let new_from: usize = new_to.saturating_sub(self.terminal_size.height as usize);
                 let mut new_items = view.get_lines(new_from, new_to);
                 while new_items.len() < self.terminal_size.height as usize {
-                    new_items.push("~".to_string());
+                    new_items.push(Item::filler());
                 }
                 self.common.items = new_items.into();
                 self.common.absolute_index = new_to - 1;
-                self.common.state.select(Some(self.common.items.len() - 1));
+                self.common.state.select(Some(new_to - 1 - new_from));
+            }
+            AppState::FileList(_) => {}
+        }
+    }
+
+    /// Writes all merged records into `all.log`, or with `filtered` only those passing the filter.
+    /// Asks where to export the records to, `all.log` in the scanned directory unless changed.
+    fn export(&mut self, prefix_filename: bool, filtered: bool) {
+        self.open_prompt(PromptKind::Export {
+            prefix_filename,
+            filtered,
+        });
+    }
+
+    /// Writes the records into `path`, asks first if it exists unless `overwrite`.
+    fn export_to(&mut self, path: PathBuf, prefix_filename: bool, filtered: bool, overwrite: bool) {
+        match &self.app_state {
+            AppState::FileList(_) => {}
+            AppState::TextView(view) => {
+                if !overwrite && path.exists() {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::Overwrite {
+                            path,
+                            prefix_filename,
+                            filtered,
+                        },
+                        text: String::new(),
+                    });
+                    return;
+                }
+                info!(
+                    "App::export - exporting to file={}, filtered={}",
+                    path.display(),
+                    filtered
+                );
+                let visible: Vec<Line>;
+                let lines = match filtered {
+                    true => {
+                        visible = view.visible_lines();
+                        &visible
+                    }
+                    false => &view.all_lines,
+                };
+                match export_to_file(&path, &view.files, lines, prefix_filename) {
+                    Ok(()) => {
+                        self.message = Some(format!(
+                            "Exported {} {}records to {}",
+                            lines.len(),
+                            if filtered { "filtered " } else { "" },
+                            path.display()
+                        ))
+                    }
+                    Err(err) => {
+                        self.error = Some(format!(
+                            "App::export - cannot export to file={}, error={}",
+                            path.display(),
+                            err
+                        ))
+                    }
+                }
+            }
+        }
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        match &self.app_state {
+            AppState::FileList(_) => {}
+            AppState::TextView(view) => {
+                // filters are edited, not retyped
+                let text = match kind {
+                    PromptKind::Search { .. } | PromptKind::GoToTime => None,
+                    PromptKind::Export { .. } => {
+                        Some(self.directory.join(EXPORT_FILENAME).display().to_string())
+                    }
+                    PromptKind::Overwrite { .. } => None,
+                    PromptKind::Include => view.filter.include.as_ref().map(|r| r.to_string()),
+                    PromptKind::Exclude => view.filter.exclude.as_ref().map(|r| r.to_string()),
+                    PromptKind::From => view.filter.from.map(format_timestamp),
+                    PromptKind::To => view.filter.to.map(format_timestamp),
+                    PromptKind::Timezone | PromptKind::Skew => view.sources_dialog.map(|i| {
+                        let correction = view.files[i].time_correction(&self.parser);
+                        match kind {
+                            PromptKind::Timezone => correction.timezone.to_string(),
+                            _ => format_skew(correction.skew),
+                        }
+                    }),
+                }
+                .unwrap_or_default();
+                self.prompt = Some(Prompt { kind, text })
+            }
+        }
+    }
+
+    fn prompt_key(&mut self, code: KeyCode) {
+        let Some(prompt) = &mut self.prompt else {
+            return;
+        };
+        match code {
+            KeyCode::Char(c) => prompt.text.push(c),
+            KeyCode::Backspace => {
+                prompt.text.pop();
             }
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                if let Some(prompt) = self.prompt.take() {
+                    match prompt.kind {
+                        PromptKind::Search { reverse } => self.search(&prompt.text, reverse),
+                        PromptKind::Include | PromptKind::Exclude => {
+                            self.set_filter_regex(&prompt.kind, &prompt.text)
+                        }
+                        PromptKind::GoToTime => self.go_to_time(&prompt.text),
+                        PromptKind::From | PromptKind::To => {
+                            self.set_time_window(&prompt.kind, &prompt.text)
+                        }
+                        PromptKind::Timezone => match parse_timezone(&prompt.text) {
+                            Ok(timezone) => self.update_correction(|c| c.timezone = timezone),
+                            Err(err) => self.error = Some(format!("App::prompt_key - {}", err)),
+                        },
+                        PromptKind::Skew => match parse_skew(&prompt.text) {
+                            Ok(skew) => self.update_correction(|c| c.skew = skew),
+                            Err(err) => self.error = Some(format!("App::prompt_key - {}", err)),
+                        },
+                        PromptKind::Export {
+                            prefix_filename,
+                            filtered,
+                        } => {
+                            if !prompt.text.is_empty() {
+                                let path = PathBuf::from(prompt.text);
+                                self.export_to(path, prefix_filename, filtered, false);
+                            }
+                        }
+                        PromptKind::Overwrite {
+                            path,
+                            prefix_filename,
+                            filtered,
+                        } => match prompt.text.trim() {
+                            "y" | "Y" => self.export_to(path, prefix_filename, filtered, true),
+                            _ => self.message = Some(String::from("Export cancelled")),
+                        },
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn search(&mut self, pattern: &str, reverse: bool) {
+        match &mut self.app_state {
             AppState::FileList(_) => {}
+            AppState::TextView(view) => {
+                if pattern.is_empty() {
+                    view.search = None;
+                    return;
+                }
+                match Regex::new(pattern) {
+                    Ok(regex) => {
+                        let matches = view.find_matches(&regex, 0);
+                        info!(
+                            "App::search - pattern={}, matches={}",
+                            pattern,
+                            matches.len()
+                        );
+                        view.search = Some(Search {
+                            regex,
+                            reverse,
+                            matches,
+                            current: None,
+                        });
+                        self.next_match(true);
+                    }
+                    Err(err) => {
+                        self.error = Some(format!(
+                            "App::search - invalid regex, pattern={}, error={}",
+                            pattern, err
+                        ))
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_filter_regex(&mut self, kind: &PromptKind, pattern: &str) {
+        let regex = match pattern.is_empty() {
+            true => None,
+            false => match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    self.error = Some(format!(
+                        "App::set_filter_regex - invalid regex, pattern={}, error={}",
+                        pattern, err
+                    ));
+                    return;
+                }
+            },
+        };
+        self.update_filter(|filter| match kind {
+            PromptKind::Include => filter.include = regex,
+            PromptKind::Exclude => filter.exclude = regex,
+            _ => {}
+        });
+    }
+
+    /// Timestamp of the record under the cursor, times typed without a date are taken relative to it.
+    fn current_timestamp(&self) -> Option<i64> {
+        match &self.app_state {
+            AppState::FileList(_) => None,
+            AppState::TextView(view) => view
+                .visible
+                .get(self.common.absolute_index)
+                .or(view.visible.first())
+                .map(|i| view.all_lines[*i].timestamp),
+        }
+    }
+
+    /// Moves the cursor onto the first visible record at or after the given time.
+    fn go_to_time(&mut self, input: &str) {
+        let time = match parse_user_time(input, self.current_timestamp()) {
+            Ok(time) => time,
+            Err(err) => {
+                self.error = Some(format!("App::go_to_time - {}", err));
+                return;
+            }
+        };
+        let position = match &self.app_state {
+            AppState::FileList(_) => return,
+            AppState::TextView(view) => {
+                // visible records are sorted by timestamp, the same as `all_lines`
+                let position = view
+                    .visible
+                    .partition_point(|i| view.all_lines[*i].timestamp < time);
+                min(position, view.len().saturating_sub(1))
+            }
+        };
+        self.jump_to(position);
+    }
+
+    fn set_time_window(&mut self, kind: &PromptKind, input: &str) {
+        let time = match input.trim().is_empty() {
+            true => None,
+            false => match parse_user_time(input, self.current_timestamp()) {
+                Ok(time) => Some(time),
+                Err(err) => {
+                    self.error = Some(format!("App::set_time_window - {}", err));
+                    return;
+                }
+            },
+        };
+        self.update_filter(|filter| match kind {
+            PromptKind::From => filter.from = time,
+            PromptKind::To => filter.to = time,
+            _ => {}
+        });
+    }
+
+    fn cycle_min_level(&mut self) {
+        self.update_filter(|filter| filter.min_level = next_min_level(filter.min_level));
+    }
+
+    /// Changes the filter of the log view and keeps the cursor on the same record, or the closest one after it.
+    fn update_filter<F: FnOnce(&mut Filter)>(&mut self, change: F) {
+        let position = match &mut self.app_state {
+            AppState::FileList(_) => return,
+            AppState::TextView(view) => {
+                change(&mut view.filter);
+                view.refilter(self.common.absolute_index)
+            }
+        };
+        self.jump_to(position);
+    }
+
+    fn toggle_gutter(&mut self) {
+        if let AppState::TextView(view) = &mut self.app_state {
+            view.show_gutter = !view.show_gutter;
+        }
+    }
+
+    fn toggle_sources_dialog(&mut self) {
+        if let AppState::TextView(view) = &mut self.app_state {
+            view.sources_dialog = match view.sources_dialog {
+                Some(_) => None,
+                None => Some(0),
+            };
+        }
+    }
+
+    fn sources_dialog_key(&mut self, code: KeyCode) {
+        let AppState::TextView(view) = &mut self.app_state else {
+            return;
+        };
+        let Some(selected) = view.sources_dialog else {
+            return;
+        };
+        match code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                view.sources_dialog = Some(min(selected + 1, view.files.len() - 1))
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                view.sources_dialog = Some(selected.saturating_sub(1))
+            }
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('s') => view.sources_dialog = None,
+            KeyCode::Char(' ') => self.update_filter(|filter| {
+                if !filter.hidden_files.remove(&selected) {
+                    filter.hidden_files.insert(selected);
+                }
+            }),
+            KeyCode::Char('+') => self.update_correction(|c| c.skew += SKEW_STEP),
+            KeyCode::Char('-') => self.update_correction(|c| c.skew -= SKEW_STEP),
+            KeyCode::Char('o') => self.open_prompt(PromptKind::Skew),
+            KeyCode::Char('z') => self.open_prompt(PromptKind::Timezone),
+            _ => {}
+        }
+    }
+
+    /// Changes the timezone or skew of the file selected in the sources dialog and merges it in again.
+    fn update_correction<F: FnOnce(&mut TimeCorrection)>(&mut self, change: F) {
+        let AppState::TextView(view) = &mut self.app_state else {
+            return;
+        };
+        let Some(selected) = view.sources_dialog else {
+            return;
+        };
+        let mut correction = view.files[selected].time_correction(&self.parser);
+        change(&mut correction);
+        view.files[selected].set_time_correction(correction);
+
+        match view.remerge_file(selected, &self.parser, self.common.absolute_index) {
+            Ok(position) => self.jump_to(position),
+            Err(err) => self.error = Some(format!("App::update_correction - {}", err)),
+        }
+    }
+
+    /// Jumps to the next match in the direction of the search, or against it with `along = false`.
+    fn next_match(&mut self, along: bool) {
+        let target = match &mut self.app_state {
+            AppState::FileList(_) => return,
+            AppState::TextView(view) => {
+                let Some(search) = &mut view.search else {
+                    return;
+                };
+                if search.matches.is_empty() {
+                    return;
+                }
+
+                // a fresh search may stay on the line under the cursor
+                let cursor = self.common.absolute_index;
+                let inclusive = search.current.is_none();
+                let forward = search.reverse != along;
+                let next = match forward {
+                    true => search
+                        .matches
+                        .partition_point(|m| *m < cursor || (!inclusive && *m == cursor)),
+                    false => search
+                        .matches
+                        .partition_point(|m| *m < cursor || (inclusive && *m == cursor)),
+                };
+                let next = match (forward, next) {
+                    (true, n) if n == search.matches.len() => 0,
+                    (true, n) => n,
+                    (false, 0) => search.matches.len() - 1,
+                    (false, n) => n - 1,
+                };
+                search.current = Some(next);
+                search.matches[next]
+            }
+        };
+        self.jump_to(target);
+    }
+
+    /// Shows a window of lines around `index` and moves the cursor onto it.
+    fn jump_to(&mut self, index: usize) {
+        match &mut self.app_state {
+            AppState::TextView(view) => {
+                let height = self.terminal_size.height as usize;
+                let new_from = index.saturating_sub(height / 2);
+                let mut new_items = view.get_lines(new_from, new_from + height);
+                if new_items.is_empty() && view.len() > 0 {
+                    return;
+                }
+                while new_items.len() < max(height, 1) {
+                    new_items.push(Item::filler());
+                }
+                self.common.items = new_items.into();
+                self.common.absolute_index = min(index, view.len().saturating_sub(1));
+                self.common
+                    .state
+                    .select(Some(self.common.absolute_index - new_from));
+            }
+            AppState::FileList(_) => {}
+        }
+    }
+
+    fn status_line(&self) -> String {
+        if let Some(prompt) = &self.prompt {
+            return format!("{}{}", prompt.prefix(), prompt.text);
+        }
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        match &self.app_state {
+            AppState::FileList(_) => String::new(),
+            AppState::TextView(view) => {
+                let mut status = format!(
+                    "line {}/{}",
+                    min(self.common.absolute_index + 1, view.len()),
+                    view.len()
+                );
+                if view.filter.is_active() {
+                    status.push_str(&format!(" (of {})", view.all_lines.len()));
+                    if let Some(include) = &view.filter.include {
+                        status.push_str(&format!("  include [{}]", include));
+                    }
+                    if let Some(exclude) = &view.filter.exclude {
+                        status.push_str(&format!("  exclude [{}]", exclude));
+                    }
+                    if let Some(level) = view.filter.min_level {
+                        status.push_str(&format!("  level >= {}", level));
+                    }
+                    if let Some(from) = view.filter.from {
+                        status.push_str(&format!("  from {}", format_timestamp(from)));
+                    }
+                    if let Some(to) = view.filter.to {
+                        status.push_str(&format!("  to {}", format_timestamp(to)));
+                    }
+                    if !view.filter.hidden_files.is_empty() {
+                        status.push_str(&format!(
+                            "  {} files hidden",
+                            view.filter.hidden_files.len()
+                        ));
+                    }
+                }
+                if let Some(follower) = &view.follower {
+                    status.push_str(&format!("  following {} files", follower.len()));
+                }
+                if let Some(search) = &view.search {
+                    match search.current {
+                        Some(current) => status.push_str(&format!(
+                            "  [{}] match {}/{}",
+                            search.regex,
+                            current + 1,
+                            search.matches.len()
+                        )),
+                        None => status.push_str(&format!(
+                            "  [{}] {} matches",
+                            search.regex,
+                            search.matches.len()
+                        )),
+                    }
+                }
+                status
+            }
         }
     }
 
+    fn toggle_file_info(&mut self) {
+        if let AppState::TextView(view) = &mut self.app_state {
+            view.details_dialog = match view.details_dialog {
+                Some(_) => None,
+                None => Some(view.file_info(&self.parser)),
+            };
+        }
+    }
+
+    fn toggle_follow(&mut self) {
+        if let AppState::TextView(view) = &mut self.app_state {
+            view.follower = match view.follower {
+                Some(_) => None,
+                None => Some(Follower::new(&view.files)),
+            };
+        }
+    }
+
+    /// Merges in what was written to the files in follow mode, stays at the end if the cursor is there.
+    fn follow(&mut self) {
+        let AppState::TextView(view) = &mut self.app_state else {
+            return;
+        };
+        let at_end = self.common.absolute_index + 1 >= view.len();
+        match view.follow(&self.parser, self.common.absolute_index) {
+            Ok(None) => {}
+            Ok(Some(_)) if at_end => self.end(),
+            Ok(Some(position)) => self.refresh_at(position),
+            Err(err) => {
+                view.follower = None;
+                self.error = Some(format!("App::follow - {}", err));
+            }
+        }
+    }
+
+    /// Shows the lines around `index` again, with the cursor on the same row of the screen.
+    fn refresh_at(&mut self, index: usize) {
+        let AppState::TextView(view) = &mut self.app_state else {
+            return;
+        };
+        let height = self.terminal_size.height as usize;
+        let index = min(index, view.len().saturating_sub(1));
+        let new_from = index.saturating_sub(self.common.state.selected().unwrap_or(0));
+        let mut new_items = view.get_lines(new_from, new_from + height);
+        while new_items.len() < max(height, 1) {
+            new_items.push(Item::filler());
+        }
+        self.common.items = new_items.into();
+        self.common.absolute_index = index;
+        self.common.state.select(Some(index - new_from));
+    }
+
     fn clear_popup(&mut self) {
         self.error = None
     }
@@ -497,7 +1653,14 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(2), Constraint::Min(0)].as_ref())
+        .constraints(
+            [
+                Constraint::Length(2),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
         .split(size);
 
     let bl = Block::default()
@@ -541,17 +1704,28 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
     f.render_widget(tabs, mid_menu_center[1]);
 
+    if let AppState::TextView(view) = &app.app_state {
+        let legend = Paragraph::new(view.legend()).style(Style::default().bg(BG_COLOR));
+        f.render_widget(legend, mid_menu_row[0]);
+    }
+
     app.terminal_size = chunks[1];
 
-    let list_items: Vec<ListItem> = match &mut app.app_state {
-        AppState::FileList(file_list) => generate_file_list(&app.file_list, file_list),
-        AppState::TextView(_) => app
+    let list_items: Vec<ListItem> = match &app.app_state {
+        AppState::FileList(file_list) => generate_file_list(
+            &app.file_list,
+            &app.file_rows,
+            file_list,
+            &app.detected_formats,
+            &app.time_spans,
+        ),
+        AppState::TextView(view) => app
             .common
             .items
             .iter()
-            .map(|i| {
+            .map(|item| {
                 let style: Style;
-                let line = String::from(i);
+                let line = &item.text;
                 if line.contains("ERROR") {
                     style = Style::default().fg(ERROR_RED_COLOR).bg(BG_COLOR);
                 } else if line.contains("WARN") {
@@ -559,7 +1733,18 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
                 } else {
                     style = Style::default().fg(FG_COLOR).bg(BG_COLOR);
                 }
-                ListItem::new(Span::from(line)).style(style)
+                let regex = view.search.as_ref().map(|s| &s.regex);
+                let mut text = highlight_matches(line, regex);
+                if let Some(source_file) = item.source_file {
+                    let (label, marker) = view.gutter(source_file);
+                    // continuation lines get the marker but not the name
+                    let blank = Span::raw(" ".repeat(label.width()));
+                    for (i, spans) in text.lines.iter_mut().enumerate() {
+                        let name = if i == 0 { label.clone() } else { blank.clone() };
+                        spans.0.splice(0..0, [marker.clone(), name]);
+                    }
+                }
+                ListItem::new(text).style(style)
             })
             .collect(),
     };
@@ -574,9 +1759,17 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
     f.render_stateful_widget(list, chunks[1], &mut app.common.state);
 
+    let status =
+        Paragraph::new(app.status_line()).style(Style::default().bg(BG_ACCENT_COLOR).fg(FG_COLOR));
+    f.render_widget(status, chunks[2]);
+
     if let Some(error_text) = &app.error {
         let block = Block::default().title("Popup").borders(Borders::ALL);
-        let area = centered_rect(60, 20, size);
+        // one line per file which failed to load
+        let area = match error_text.lines().count() > 3 {
+            true => centered_rect(80, 80, size),
+            false => centered_rect(60, 20, size),
+        };
         f.render_widget(tui::widgets::Clear, area); //this clears out the background
 
         let text = error_text.to_owned() + "\n\nPress 'Esc' to close this popup";
@@ -589,12 +1782,44 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         f.render_widget(paragraph, area);
     }
 
+    if let Some(loader) = &app.loader {
+        let block = Block::default()
+            .title("Loading - 'Esc' cancel")
+            .borders(Borders::ALL)
+            .style(Style::default().bg(BG_ACCENT_COLOR).fg(FG_ACCENT_COLOR));
+        let area = centered_rect(80, 60, size);
+        f.render_widget(tui::widgets::Clear, area); //this clears out the background
+        f.render_widget(block, area);
+
+        let inner = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+            .split(area);
+        let ratio = loader.ratio();
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(FG_ACCENT_COLOR).bg(BG_ACCENT_COLOR))
+            .ratio(ratio)
+            .label(format!("{:.0}%", ratio * 100.0));
+        f.render_widget(gauge, inner[0]);
+
+        let paragraph = Paragraph::new(loader.status())
+            .style(Style::default().bg(BG_ACCENT_COLOR).fg(FG_ACCENT_COLOR))
+            .alignment(Alignment::Left)
+            .wrap(Wrap { trim: false });
+        f.render_widget(paragraph, inner[1]);
+    }
+
     match &mut app.app_state {
         AppState::FileList(_) => {}
         AppState::TextView(view) => {
             if let Some(text) = &view.details_dialog {
                 let block = Block::default().title("Popup").borders(Borders::ALL);
-                let area = centered_rect(60, 20, size);
+                // the file info holds four lines per file
+                let area = match text.lines().count() > 3 {
+                    true => centered_rect(80, 80, size),
+                    false => centered_rect(60, 20, size),
+                };
                 f.render_widget(tui::widgets::Clear, area); //this clears out the background
 
                 let paragraph = Paragraph::new(text.clone())
@@ -604,67 +1829,334 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
                     .wrap(Wrap { trim: false });
                 f.render_widget(paragraph, area);
             }
+
+            if let Some(selected) = view.sources_dialog {
+                let block = Block::default()
+                    .title("Sources - 'Space' toggle, '+'/'-' skew, 'o' set skew, 'z' timezone, 'Esc' close")
+                    .borders(Borders::ALL);
+                let area = centered_rect(60, 40, size);
+                f.render_widget(tui::widgets::Clear, area); //this clears out the background
+
+                let filenames: Vec<String> = view.files.iter().map(|f| f.filename()).collect();
+                let names = unique_names(&filenames);
+                let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+                let items: Vec<ListItem> = view
+                    .files
+                    .iter()
+                    .zip(names.iter())
+                    .enumerate()
+                    .map(|(i, (file, name))| {
+                        let marker = match view.filter.hidden_files.contains(&i) {
+                            true => " ",
+                            false => "x",
+                        };
+                        ListItem::new(format!(
+                            "[{}] {:width$}  {}",
+                            marker,
+                            name,
+                            file.time_correction(&app.parser),
+                            width = width
+                        ))
+                    })
+                    .collect();
+                let list = List::new(items)
+                    .style(Style::default().bg(BG_ACCENT_COLOR).fg(FG_COLOR))
+                    .block(block)
+                    .highlight_style(Style::default().fg(FG_ACCENT_COLOR).bg(BG_COLOR));
+                let mut state = ListState::default();
+                state.select(Some(selected));
+                f.render_stateful_widget(list, area, &mut state);
+            }
         }
     }
 }
 
+/// Splits `text` into lines and emphasizes everything matching `regex`.
+fn highlight_matches<'a>(text: &'a str, regex: Option<&Regex>) -> Text<'a> {
+    let Some(regex) = regex else {
+        return Text::from(text);
+    };
+
+    let highlight = Style::default()
+        .fg(BG_COLOR)
+        .bg(FG_ACCENT_COLOR)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let lines: Vec<Spans> = text
+        .split('\n')
+        .map(|line| {
+            let mut spans: Vec<Span> = Vec::new();
+            let mut last: usize = 0;
+            for m in regex.find_iter(line) {
+                if m.start() > last {
+                    spans.push(Span::raw(&line[last..m.start()]));
+                }
+                spans.push(Span::styled(m.as_str(), highlight));
+                last = m.end();
+            }
+            if last < line.len() {
+                spans.push(Span::raw(&line[last..]));
+            }
+            Spans::from(spans)
+        })
+        .collect();
+    Text::from(lines)
+}
+
+/// A member of the archive `entry`, which is listed already as its row shows.
+fn member_of(file_list: &[FileEntry], entry: usize, member: usize) -> &FileEntry {
+    &file_list[entry].members.as_ref().unwrap()[member]
+}
+
+/// How many of the files to load for `entry` are selected, and how many there are.
+/// An archive counts as one file until its members are listed.
+fn selection(entry: &FileEntry, loaded_items: &HashSet<String>) -> (usize, usize) {
+    let is_loaded = |f: &FileEntry| loaded_items.contains(&f.filename);
+    match &entry.members {
+        Some(members) if entry.archive => (
+            members.iter().filter(|m| is_loaded(m)).count(),
+            members.len(),
+        ),
+        _ => (is_loaded(entry) as usize, 1),
+    }
+}
+
+/// `x` when all files are selected, `-` when some are.
+fn selection_marker(entries: &[&FileEntry], loaded_items: &HashSet<String>) -> &'static str {
+    let (selected, total) = entries
+        .iter()
+        .map(|entry| selection(entry, loaded_items))
+        .fold((0, 0), |(s, t), (selected, total)| {
+            (s + selected, t + total)
+        });
+    match selected {
+        0 => " ",
+        n if n == total => "x",
+        _ => "-",
+    }
+}
+
+/// Selects all of `files`, or none if all of them were.
+fn flip_all(loaded_items: &mut HashSet<String>, files: Vec<String>) {
+    if files.iter().all(|f| loaded_items.contains(f)) {
+        for file in &files {
+            loaded_items.remove(file);
+        }
+    } else {
+        loaded_items.extend(files);
+    }
+}
+
 fn generate_file_list<'a>(
-    app_file_list: &'a Vec<FileEntry>,
+    app_file_list: &'a [FileEntry],
+    file_rows: &[FileRow],
     file_list: &FileListMenu,
+    detected_formats: &HashMap<String, String>,
+    time_spans: &HashMap<String, (i64, i64)>,
 ) -> Vec<ListItem<'a>> {
+    // files are indented below their directory
+    let indent = |depth: usize| "  ".repeat(depth);
+    let family_name = |path: &str, expanded: bool, members: usize| {
+        let arrow = if expanded { "▾" } else { "▸" };
+        let name = path.rsplit('/').next().unwrap_or(path);
+        format!("{} {} (+{} rotated)", arrow, name, members - 1)
+    };
+    let archive_name = |entry: &FileEntry, expanded: bool| {
+        let arrow = if expanded { "▾" } else { "▸" };
+        format!("{} {}", arrow, short_filename(&entry.filename))
+    };
+    let member_name = |member: &FileEntry| {
+        let (_, name) = decompress::split_member(&member.filename);
+        name.unwrap_or_default().to_string()
+    };
     let mut max_filename_len: usize = 0;
-    for f in app_file_list {
-        let filename_string: String = String::from_str(
-            Path::new(&f.filename)
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap(),
-        )
-        .unwrap();
-
-        if filename_string.len() > max_filename_len {
-            max_filename_len = filename_string.len();
-        }
+    for row in file_rows {
+        let filename_string = match row {
+            FileRow::File { entry, depth } => {
+                indent(*depth) + &short_filename(&app_file_list[*entry].filename)
+            }
+            FileRow::Archive {
+                entry,
+                depth,
+                expanded,
+            } => indent(*depth) + &archive_name(&app_file_list[*entry], *expanded),
+            FileRow::Member {
+                entry,
+                member,
+                depth,
+            } => indent(*depth) + &member_name(member_of(app_file_list, *entry, *member)),
+            FileRow::Family {
+                path,
+                depth,
+                entries,
+                expanded,
+            } => indent(*depth) + &family_name(path, *expanded, entries.len()),
+            FileRow::Listing { depth, .. } => indent(*depth) + LISTING,
+            FileRow::Dir { .. } => continue,
+        };
+        max_filename_len = max(max_filename_len, filename_string.chars().count());
     }
 
     max_filename_len += 5;
 
-    app_file_list
+    file_rows
         .iter()
-        .map(|i| {
-            let loaded_marker = if file_list
-                .loaded_items
-                .contains(&App::to_abs_path(&i.filename))
-            {
+        .map(|row| {
+            let (entry, depth, name) = match row {
+                FileRow::File { entry, depth } => {
+                    let entry = &app_file_list[*entry];
+                    (entry, *depth, short_filename(&entry.filename))
+                }
+                FileRow::Member {
+                    entry,
+                    member,
+                    depth,
+                } => {
+                    let member = member_of(app_file_list, *entry, *member);
+                    (member, *depth, member_name(member))
+                }
+                FileRow::Archive {
+                    entry,
+                    depth,
+                    expanded,
+                } => {
+                    let entry = &app_file_list[*entry];
+                    let marker = selection_marker(&[entry], &file_list.loaded_items);
+                    let mut filename_string = indent(*depth) + &archive_name(entry, *expanded);
+                    let len = filename_string.chars().count();
+                    if len < max_filename_len {
+                        filename_string += " ".repeat(max_filename_len - len).as_str();
+                    }
+                    let count = match entry.members.as_ref().map(|m| m.len()) {
+                        Some(1) => String::from(" 1 file"),
+                        Some(n) => format!(" {} files", n),
+                        None => String::new(),
+                    };
+                    return ListItem::new(Span::from(format!(
+                        "[{}] {} ({} B){}",
+                        marker, filename_string, entry.file_size, count
+                    )))
+                    .style(Style::default().fg(FG_COLOR).bg(BG_COLOR));
+                }
+                FileRow::Listing { depth, .. } => {
+                    return ListItem::new(Span::from(format!("    {}{}", indent(*depth), LISTING)))
+                        .style(Style::default().fg(FG_ACCENT_COLOR).bg(BG_COLOR));
+                }
+                FileRow::Dir {
+                    path,
+                    depth,
+                    files,
+                    collapsed,
+                } => {
+                    let prefix = format!("{}/", path);
+                    let below: Vec<&FileEntry> = app_file_list
+                        .iter()
+                        .filter(|f| f.dir == *path || f.dir.starts_with(&prefix))
+                        .collect();
+                    let marker = selection_marker(&below, &file_list.loaded_items);
+                    let name = path.rsplit('/').next().unwrap_or(path);
+                    let arrow = if *collapsed { "▸" } else { "▾" };
+                    let count = match files {
+                        1 => String::from("1 file"),
+                        n => format!("{} files", n),
+                    };
+                    return ListItem::new(Span::from(format!(
+                        "[{}] {}{} {}/ ({})",
+                        marker,
+                        indent(*depth),
+                        arrow,
+                        name,
+                        count
+                    )))
+                    .style(Style::default().fg(FG_ACCENT_COLOR).bg(BG_COLOR));
+                }
+                // the combined size of the files and the time span of those it is known of, e.g. plain or cached ones
+                FileRow::Family {
+                    path,
+                    depth,
+                    entries,
+                    expanded,
+                } => {
+                    let members: Vec<&FileEntry> =
+                        entries.iter().map(|e| &app_file_list[*e]).collect();
+                    let marker = selection_marker(&members, &file_list.loaded_items);
+                    let total: u64 = members.iter().map(|entry| entry.file_size).sum();
+                    let spans: Vec<&(i64, i64)> = members
+                        .iter()
+                        .filter_map(|entry| time_spans.get(&entry.filename))
+                        .collect();
+                    let span = spans.iter().fold(
+                        None,
+                        |span: Option<(i64, i64)>, (first, last)| match span {
+                            Some((a, b)) => Some((min(a, *first), max(b, *last))),
+                            None => Some((*first, *last)),
+                        },
+                    );
+                    let seconds = |t: i64| {
+                        let time = format_timestamp(t);
+                        time.get(..19).map(String::from).unwrap_or(time)
+                    };
+                    // the span of the others is not known, it may be wider
+                    let span = match (span, members.len() - spans.len()) {
+                        (Some((first, last)), 0) => {
+                            format!(" {} – {}", seconds(first), seconds(last))
+                        }
+                        (Some((first, last)), unknown) => format!(
+                            " {} – {} (+{} unknown)",
+                            seconds(first),
+                            seconds(last),
+                            unknown
+                        ),
+                        (None, _) => String::new(),
+                    };
+                    let mut filename_string =
+                        indent(*depth) + &family_name(path, *expanded, members.len());
+                    let len = filename_string.chars().count();
+                    if len < max_filename_len {
+                        filename_string += " ".repeat(max_filename_len - len).as_str();
+                    }
+                    return ListItem::new(Span::from(format!(
+                        "[{}] {} ({} B){}",
+                        marker, filename_string, total, span
+                    )))
+                    .style(Style::default().fg(FG_COLOR).bg(BG_COLOR));
+                }
+            };
+            let loaded_marker = if file_list.loaded_items.contains(&entry.filename) {
                 "x"
             } else {
                 " "
             };
-            let mut filename_string = String::from_str(
-                Path::new(&i.filename)
-                    .file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap(),
-            )
-            .unwrap();
-            if filename_string.len() < max_filename_len {
-                filename_string +=
-                    String::from_utf8(vec![b' '; max_filename_len - filename_string.len()])
-                        .unwrap()
-                        .as_str();
-            }
-            ListItem::new(Span::from(format!(
-                "[{}] {} ({} B)",
-                loaded_marker, filename_string, i.file_size
-            )))
-            .style(Style::default().fg(FG_COLOR).bg(BG_COLOR))
+            let mut filename_string = indent(depth) + &name;
+            let len = filename_string.chars().count();
+            if len < max_filename_len {
+                filename_string += " ".repeat(max_filename_len - len).as_str();
+            }
+            let format = match detected_formats.get(&entry.filename) {
+                Some(format) => format!(" {}", format),
+                None => String::new(),
+            };
+            let mut spans = vec![Span::from(format!(
+                "[{}] {} ({} B){}",
+                loaded_marker, filename_string, entry.file_size, format
+            ))];
+            if entry.binary {
+                spans.push(Span::styled(
+                    "  binary?",
+                    Style::default().fg(WARN_YELLOW_COLOR),
+                ));
+            }
+            ListItem::new(Spans::from(spans)).style(Style::default().fg(FG_COLOR).bg(BG_COLOR))
         })
         .collect()
 }
 
-pub fn run_app(folder_to_run: &String) -> Result<(), io::Error> {
+pub fn run_app(
+    folder_to_run: &String,
+    filter: Filter,
+    parser: TimestampParser,
+    cache: Option<IndexCache>,
+    scan: ScanOptions,
+) -> Result<(), io::Error> {
     crossterm::terminal::enable_raw_mode()?;
     let mut stdout = io::stdout();
     crossterm::execute!(
@@ -676,19 +2168,52 @@ pub fn run_app(folder_to_run: &String) -> Result<(), io::Error> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app: App = App::new(Path::new(folder_to_run), terminal.size()?)?;
+    let mut app: App = App::new(
+        Path::new(folder_to_run),
+        terminal.size()?,
+        filter,
+        parser,
+        cache,
+        scan,
+    )?;
 
     loop {
+        app.poll_loader();
         terminal.draw(|f| ui(f, &mut app))?;
 
         if crossterm::event::poll(Duration::from_millis(100))? {
             if let crossterm::event::Event::Key(key) = crossterm::event::read()? {
+                app.message = None;
+                if app.loader.is_some() {
+                    match key.code {
+                        // the runtime waits for the loading tasks when it shuts down
+                        crossterm::event::KeyCode::Char('q') => {
+                            app.cancel_loading();
+                            break;
+                        }
+                        crossterm::event::KeyCode::Esc => app.cancel_loading(),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.prompt.is_some() {
+                    app.prompt_key(key.code);
+                    continue;
+                }
+                if let AppState::TextView(view) = &app.app_state {
+                    if view.sources_dialog.is_some() {
+                        app.sources_dialog_key(key.code);
+                        continue;
+                    }
+                }
                 match key.code {
                     crossterm::event::KeyCode::Char('q') => break,
                     crossterm::event::KeyCode::Char('j') => app.select_next(),
                     crossterm::event::KeyCode::Char('k') => app.select_previous(),
                     crossterm::event::KeyCode::Down => app.select_next(),
                     crossterm::event::KeyCode::Up => app.select_previous(),
+                    crossterm::event::KeyCode::Left => app.collapse(true),
+                    crossterm::event::KeyCode::Right => app.collapse(false),
                     crossterm::event::KeyCode::Char(' ') => app.flip_current(),
                     crossterm::event::KeyCode::Enter => app.enter(),
                     crossterm::event::KeyCode::Backspace => app.go_to_file_list(),
@@ -696,10 +2221,34 @@ pub fn run_app(folder_to_run: &String) -> Result<(), io::Error> {
                     crossterm::event::KeyCode::PageDown => app.page_down(),
                     crossterm::event::KeyCode::Home => app.home(),
                     crossterm::event::KeyCode::End => app.end(),
+                    crossterm::event::KeyCode::Char('e') => app.export(false, false),
+                    crossterm::event::KeyCode::Char('E') => app.export(true, false),
+                    crossterm::event::KeyCode::Char('x') => app.export(false, true),
+                    crossterm::event::KeyCode::Char('X') => app.export(true, true),
+                    crossterm::event::KeyCode::Char('/') => {
+                        app.open_prompt(PromptKind::Search { reverse: false })
+                    }
+                    crossterm::event::KeyCode::Char('?') => {
+                        app.open_prompt(PromptKind::Search { reverse: true })
+                    }
+                    crossterm::event::KeyCode::Char('f') => app.open_prompt(PromptKind::Include),
+                    crossterm::event::KeyCode::Char('F') => app.open_prompt(PromptKind::Exclude),
+                    crossterm::event::KeyCode::Char('l') => app.cycle_min_level(),
+                    crossterm::event::KeyCode::Char('g') => app.open_prompt(PromptKind::GoToTime),
+                    crossterm::event::KeyCode::Char('[') => app.open_prompt(PromptKind::From),
+                    crossterm::event::KeyCode::Char(']') => app.open_prompt(PromptKind::To),
+                    crossterm::event::KeyCode::Char('s') => app.toggle_sources_dialog(),
+                    crossterm::event::KeyCode::Char('c') => app.toggle_gutter(),
+                    crossterm::event::KeyCode::Char('i') => app.toggle_file_info(),
+                    crossterm::event::KeyCode::Char('t') => app.toggle_follow(),
+                    crossterm::event::KeyCode::Char('n') => app.next_match(true),
+                    crossterm::event::KeyCode::Char('N') => app.next_match(false),
                     crossterm::event::KeyCode::Esc => app.clear_popup(),
                     _ => {}
                 }
             }
+        } else {
+            app.follow();
         }
     }
 
@@ -0,0 +1,362 @@
+use super::*;
+use crate::text::fixtures::TempDir;
+use std::fs;
+use tui::backend::TestBackend;
+
+fn screen(terminal: &Terminal<TestBackend>) -> String {
+    let buffer = terminal.backend().buffer();
+    buffer
+        .content()
+        .chunks(buffer.area.width as usize)
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell.symbol.as_str())
+                .collect::<String>()
+                + "\n"
+        })
+        .collect()
+}
+
+#[test]
+fn truncated_file_on_disk() {
+    let dir = TempDir::new("app_truncated");
+    // more than one block, the records at the start are read from disk again
+    let old: String = (0..40000)
+        .map(|i| format!("2023-05-03 10:00:00.000 old record {}\n", i))
+        .collect();
+    let path = dir.write("app.log", old);
+    let parser = TimestampParser::default();
+    let file = FileWithLines::open(
+        path.to_string_lossy().into_owned(),
+        &LoadProgress::default(),
+        None,
+    )
+    .unwrap();
+    let lines = file.get_annotated_lines(0, &parser).unwrap();
+
+    let size = Rect::new(0, 0, 80, 10);
+    let mut app = App::new(
+        dir.path(),
+        size,
+        Filter::default(),
+        parser,
+        None,
+        ScanOptions::default(),
+    )
+    .unwrap();
+    app.open_view(vec![file], lines);
+    app.toggle_follow();
+
+    let mut terminal = Terminal::new(TestBackend::new(size.width, size.height)).unwrap();
+    // the block at the end is the one in memory then
+    app.end();
+    // copytruncate, then new text shorter than the old one
+    fs::write(&path, "2023-05-03 10:00:02.000 new\n").unwrap();
+    app.refresh_at(0);
+    terminal.draw(|f| ui(f, &mut app)).unwrap();
+    let shown = screen(&terminal);
+    assert!(!shown.contains("old record"), "{}", shown);
+    assert!(shown.contains("<cannot read record: "), "{}", shown);
+
+    // following the file drops its old records
+    app.follow();
+    assert_eq!(app.error, None);
+    terminal.draw(|f| ui(f, &mut app)).unwrap();
+    let shown = screen(&terminal);
+    assert!(!shown.contains("<cannot read record"), "{}", shown);
+    assert!(shown.contains("10:00:02.000 new"), "{}", shown);
+    assert!(shown.contains("line 1/1"), "{}", shown);
+}
+
+#[test]
+fn truncated_file_keeps_leading_lines() {
+    let dir = TempDir::new("app_truncated_leading");
+    let old: String = (0..40000)
+        .map(|i| format!("2023-05-03 10:00:00.000 old record {}\n", i))
+        .collect();
+    let path = dir.write("app.log", old);
+    let parser = TimestampParser::default();
+    let file = FileWithLines::open(
+        path.to_string_lossy().into_owned(),
+        &LoadProgress::default(),
+        None,
+    )
+    .unwrap();
+    let lines = file.get_annotated_lines(0, &parser).unwrap();
+
+    let size = Rect::new(0, 0, 80, 10);
+    let mut app = App::new(
+        dir.path(),
+        size,
+        Filter::default(),
+        parser,
+        None,
+        ScanOptions::default(),
+    )
+    .unwrap();
+    app.open_view(vec![file], lines);
+    app.toggle_follow();
+    app.end();
+
+    // the new text starts with a line without a timestamp, its first record comes later
+    fs::write(&path, "starting\n").unwrap();
+    app.follow();
+    assert_eq!(app.error, None);
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    std::io::Write::write_all(&mut file, b"2023-05-03 10:00:02.000 new\n").unwrap();
+    drop(file);
+    app.follow();
+    assert_eq!(app.error, None);
+
+    let mut terminal = Terminal::new(TestBackend::new(size.width, size.height)).unwrap();
+    terminal.draw(|f| ui(f, &mut app)).unwrap();
+    let shown = screen(&terminal);
+    assert!(shown.contains("starting"), "{}", shown);
+    assert!(shown.contains("10:00:02.000 new"), "{}", shown);
+    assert!(shown.contains("line 2/2"), "{}", shown);
+}
+
+#[test]
+fn file_list_renders_without_the_files() {
+    let dir = TempDir::new("app_file_list");
+    let path = dir.write("app.log", "2023-05-03 10:00:00.000 one\n");
+    let size = Rect::new(0, 0, 80, 10);
+    let mut app = App::new(
+        dir.path(),
+        size,
+        Filter::default(),
+        TimestampParser::default(),
+        None,
+        ScanOptions::default(),
+    )
+    .unwrap();
+    app.flip_current();
+
+    // the file list is drawn from the paths found by the scan, without looking at the files again
+    fs::remove_file(&path).unwrap();
+    let mut terminal = Terminal::new(TestBackend::new(size.width, size.height)).unwrap();
+    terminal.draw(|f| ui(f, &mut app)).unwrap();
+    let shown = screen(&terminal);
+    assert!(shown.contains("[x]"), "{}", shown);
+    assert!(shown.contains("app.log"), "{}", shown);
+}
+
+#[tokio::test]
+async fn family_with_archives_is_listed_when_loading() {
+    let dir = TempDir::new("app_family");
+    dir.write("app.log", "2023-05-03 10:00:02.000 current\n");
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, text) in [
+        ("app/one.log", "2023-05-03 10:00:00.000 one\n"),
+        ("app/two.log", "2023-05-03 10:00:01.000 two\n"),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(text.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, text.as_bytes())
+            .unwrap();
+    }
+    dir.write("app.log.1.tar", builder.into_inner().unwrap());
+
+    let size = Rect::new(0, 0, 80, 10);
+    let mut app = App::new(
+        dir.path(),
+        size,
+        Filter::default(),
+        TimestampParser::default(),
+        None,
+        ScanOptions::default(),
+    )
+    .unwrap();
+    assert!(matches!(app.file_rows[0], FileRow::Family { .. }));
+    app.flip_current();
+    // the archive is selected as a whole, without decompressing it
+    let archive = app.file_list.iter().find(|e| e.archive).unwrap();
+    assert!(archive.members.is_none());
+
+    app.enter();
+    while app.loader.is_some() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        app.poll_loader();
+    }
+    let AppState::TextView(view) = &app.app_state else {
+        panic!("no log view, error={:?}", app.error);
+    };
+    let records: Vec<String> = view.get_lines(0, 3).into_iter().map(|i| i.text).collect();
+    assert_eq!(
+        records,
+        vec![
+            "2023-05-03 10:00:00.000 one",
+            "2023-05-03 10:00:01.000 two",
+            "2023-05-03 10:00:02.000 current",
+        ]
+    );
+
+    // back in the file list the archive is still selected as a whole
+    app.go_to_file_list();
+    let archive = app.file_list.iter().find(|e| e.archive).unwrap();
+    let AppState::FileList(file_list) = &app.app_state else {
+        panic!("no file list");
+    };
+    assert!(file_list.loaded_items.contains(&archive.filename));
+    assert_eq!(file_list.loaded_items.len(), 2);
+}
+
+#[test]
+fn family_span_counts_files_of_unknown_span() {
+    let dir = TempDir::new("app_family_span");
+    dir.write(
+        "app.log",
+        "2023-05-03 10:00:02.000 current\n2023-05-03 10:00:03.000 last\n",
+    );
+    let mut builder = tar::Builder::new(Vec::new());
+    let text = "2023-05-03 10:00:00.000 rotated\n";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(text.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "app.log", text.as_bytes())
+        .unwrap();
+    dir.write("app.log.1.tar", builder.into_inner().unwrap());
+
+    let size = Rect::new(0, 0, 120, 10);
+    let mut app = App::new(
+        dir.path(),
+        size,
+        Filter::default(),
+        TimestampParser::default(),
+        None,
+        ScanOptions::default(),
+    )
+    .unwrap();
+    // the archive would have to be decompressed for its span
+    let mut terminal = Terminal::new(TestBackend::new(size.width, size.height)).unwrap();
+    terminal.draw(|f| ui(f, &mut app)).unwrap();
+    let shown = screen(&terminal);
+    assert!(
+        shown.contains("2023-05-03 10:00:02 – 2023-05-03 10:00:03 (+1 unknown)"),
+        "{}",
+        shown
+    );
+}
+
+#[tokio::test]
+async fn archive_members_are_listed_in_the_background() {
+    let dir = TempDir::new("app_listing");
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, text) in [
+        ("app/one.log", "2023-05-03 10:00:00.000 one\n"),
+        ("app/two.log", "2023-05-03 10:00:01.000 two\n"),
+    ] {
+        writer
+            .start_file(name, zip::write::FileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut writer, text.as_bytes()).unwrap();
+    }
+    dir.write("bundle.zip", writer.finish().unwrap().into_inner());
+
+    let size = Rect::new(0, 0, 80, 10);
+    let mut app = App::new(
+        dir.path(),
+        size,
+        Filter::default(),
+        TimestampParser::default(),
+        None,
+        ScanOptions::default(),
+    )
+    .unwrap();
+    // the archive selected as a whole is selected by its members once they are listed
+    app.flip_current();
+    app.collapse(false);
+    assert!(matches!(
+        app.file_rows[1],
+        FileRow::Listing { entry: 0, .. }
+    ));
+    let mut terminal = Terminal::new(TestBackend::new(size.width, size.height)).unwrap();
+    terminal.draw(|f| ui(f, &mut app)).unwrap();
+    assert!(screen(&terminal).contains(LISTING));
+
+    app.common.state.select(Some(1));
+    while !app.listings.is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        app.poll_loader();
+    }
+    assert_eq!(app.error, None);
+    assert_eq!(app.file_rows.len(), 3);
+    assert_eq!(app.common.state.selected(), Some(1));
+    terminal.draw(|f| ui(f, &mut app)).unwrap();
+    let shown = screen(&terminal);
+    assert!(shown.contains("app/one.log"), "{}", shown);
+    assert!(shown.contains("app/two.log"), "{}", shown);
+    let AppState::FileList(file_list) = &app.app_state else {
+        panic!("no file list");
+    };
+    assert_eq!(file_list.loaded_items.len(), 2);
+    assert!(file_list
+        .loaded_items
+        .iter()
+        .all(|item| item.ends_with("one.log") || item.ends_with("two.log")));
+}
+
+#[test]
+fn export_asks_before_overwriting() {
+    let dir = TempDir::new("app_export");
+    let path = dir.write("app.log", "2023-05-03 10:00:00.000 one\n");
+    let parser = TimestampParser::default();
+    let file = FileWithLines::new(
+        path.to_string_lossy().into_owned(),
+        String::from("2023-05-03 10:00:00.000 one\n"),
+    );
+    let lines = file.get_annotated_lines(0, &parser).unwrap();
+    let mut app = App::new(
+        dir.path(),
+        Rect::new(0, 0, 80, 10),
+        Filter::default(),
+        parser,
+        None,
+        ScanOptions::default(),
+    )
+    .unwrap();
+    app.open_view(vec![file], lines);
+    let export = dir.path().join(EXPORT_FILENAME);
+
+    // the path defaults to all.log in the scanned directory
+    app.export(false, false);
+    assert_eq!(
+        app.status_line(),
+        format!("export to: {}", export.display())
+    );
+    app.prompt_key(KeyCode::Enter);
+    assert_eq!(app.error, None);
+    assert!(app
+        .message
+        .as_ref()
+        .unwrap()
+        .starts_with("Exported 1 records"));
+    assert_eq!(
+        fs::read_to_string(&export).unwrap(),
+        "2023-05-03 10:00:00.000 one\n"
+    );
+
+    // an existing file is only overwritten once confirmed
+    fs::write(&export, "kept\n").unwrap();
+    app.export(true, false);
+    app.prompt_key(KeyCode::Enter);
+    assert!(app.status_line().ends_with("exists, overwrite? (y/n): "));
+    app.prompt_key(KeyCode::Char('n'));
+    app.prompt_key(KeyCode::Enter);
+    assert_eq!(app.message.as_deref(), Some("Export cancelled"));
+    assert_eq!(fs::read_to_string(&export).unwrap(), "kept\n");
+
+    app.export(true, false);
+    app.prompt_key(KeyCode::Enter);
+    app.prompt_key(KeyCode::Char('y'));
+    app.prompt_key(KeyCode::Enter);
+    assert_eq!(
+        fs::read_to_string(&export).unwrap(),
+        "app.log:2023-05-03 10:00:00.000 one\n"
+    );
+}
@@ -0,0 +1,462 @@
+#[cfg(test)]
+mod test;
+
+use crate::decompress;
+use crate::lineindex::LineIndex;
+use crate::mergeline::Line;
+use crate::text::FileWithLines;
+use chrono::{DateTime, Utc};
+use log::*;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Starts every entry, a new version makes the entries of older ones stale.
+const MAGIC: &[u8] = b"logchuck-index-5\n";
+
+/// Bytes hashed at the start and at the end of the indexed part of a file.
+const HASH_BLOCK: u64 = 64 * 1024;
+
+/// Entries beyond this total size are removed on `IndexCache::store`, those used last first.
+const MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Entries not used for this long are removed on `IndexCache::store`.
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// 64-bit FNV-1a, unlike `DefaultHasher` its hashes stay the same across Rust versions.
+/// Entry names, content hashes and parser fingerprints are stored with it.
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Hashed with its length first, so consecutive strings do not run into each other.
+    pub fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Line breaks and records of a file from an earlier load.
+#[derive(Debug, Default, PartialEq)]
+pub struct FileIndex {
+    pub path: String,
+    /// Read as it is, such a file may have grown since and its index still describes the start of it.
+    pub plain: bool,
+    /// Bytes indexed of the file on disk, or size of the compressed file or archive holding it.
+    pub file_size: u64,
+    /// Modification time in microseconds.
+    pub modified: i64,
+    /// Of the first and last bytes within `file_size`.
+    pub hash: u64,
+    /// `TimestampParser::fingerprint` of the file when its timestamps were parsed.
+    pub parser_key: u64,
+    pub detected_format: Option<String>,
+    pub out_of_order: usize,
+    /// Earliest and latest timestamp of the records, kept before them to be read on its own.
+    pub span: (i64, i64),
+    pub line_index: LineIndex,
+    pub records: Vec<Line>,
+}
+
+impl FileIndex {
+    /// Describes `file` as it was loaded, with its `records`.
+    pub fn new(file: &FileWithLines, records: &[Line], parser_key: u64) -> io::Result<FileIndex> {
+        let path = file.filename();
+        let (archive, _) = decompress::split_member(&path);
+        let metadata = fs::metadata(archive)?;
+        let plain = decompress::is_plain(&path)?;
+        let file_size = match plain {
+            // the file may have grown while it was loaded
+            true => file.raw_size() as u64,
+            false => metadata.len(),
+        };
+
+        Ok(FileIndex {
+            plain,
+            file_size,
+            modified: DateTime::<Utc>::from(metadata.modified()?).timestamp_micros(),
+            hash: content_hash(archive, file_size)?,
+            parser_key,
+            detected_format: file.detected_format().map(String::from),
+            out_of_order: file.out_of_order(),
+            // sorted by timestamp
+            span: match (records.first(), records.last()) {
+                (Some(first), Some(last)) => (first.timestamp, last.timestamp),
+                _ => (0, 0),
+            },
+            line_index: file.line_index().clone(),
+            records: records.to_vec(),
+            path,
+        })
+    }
+
+    /// Whether the file on disk is the one indexed, or a plain file which was only appended to since.
+    fn describes_file(&self) -> io::Result<bool> {
+        let (archive, _) = decompress::split_member(&self.path);
+        let metadata = fs::metadata(archive)?;
+        let unchanged = match self.plain {
+            true => metadata.len() >= self.file_size,
+            false => {
+                metadata.len() == self.file_size
+                    && DateTime::<Utc>::from(metadata.modified()?).timestamp_micros()
+                        == self.modified
+            }
+        };
+        Ok(unchanged && content_hash(archive, self.file_size)? == self.hash)
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        write_str(out, &self.path)?;
+        write_u64(out, self.plain as u64)?;
+        write_u64(out, self.file_size)?;
+        write_i64(out, self.modified)?;
+        out.write_all(&self.hash.to_le_bytes())?;
+        out.write_all(&self.parser_key.to_le_bytes())?;
+        write_str(out, self.detected_format.as_deref().unwrap_or_default())?;
+        write_u64(out, self.out_of_order as u64)?;
+        write_i64(out, self.span.0)?;
+        write_i64(out, self.span.1)?;
+
+        // offsets and timestamps are stored as differences to the previous one, mostly a byte or two
+        write_u64(out, self.line_index.starts.len() as u64)?;
+        let mut previous = 0;
+        for start in &self.line_index.starts {
+            write_u64(out, (start - previous) as u64)?;
+            previous = *start;
+        }
+        write_u64(out, self.line_index.breaks as u64)?;
+        write_u64(out, self.line_index.tail as u64)?;
+        write_u64(out, self.line_index.size as u64)?;
+        write_u64(out, self.records.len() as u64)?;
+        let (mut timestamp, mut index) = (0, 0);
+        for record in &self.records {
+            write_i64(out, record.timestamp - timestamp)?;
+            write_i64(out, record.index as i64 - index)?;
+            write_u64(out, record.line_count as u64)?;
+            (timestamp, index) = (record.timestamp, record.index as i64);
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn read(input: &mut impl Read) -> io::Result<FileIndex> {
+        let mut index = FileIndex::read_header(input)?;
+        index.read_body(input)?;
+        Ok(index)
+    }
+
+    /// Reads all but the line index and records.
+    fn read_header(input: &mut impl Read) -> io::Result<FileIndex> {
+        let mut magic = [0; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an index of this version",
+            ));
+        }
+
+        Ok(FileIndex {
+            path: read_str(input)?,
+            plain: read_u64(input)? != 0,
+            file_size: read_u64(input)?,
+            modified: read_i64(input)?,
+            hash: read_fixed(input)?,
+            parser_key: read_fixed(input)?,
+            detected_format: Some(read_str(input)?).filter(|f| !f.is_empty()),
+            out_of_order: read_u64(input)? as usize,
+            span: (read_i64(input)?, read_i64(input)?),
+            ..Default::default()
+        })
+    }
+
+    fn read_body(&mut self, input: &mut impl Read) -> io::Result<()> {
+        let mut starts: Vec<usize> = Vec::new();
+        let mut previous = 0;
+        for _ in 0..read_u64(input)? {
+            previous += read_u64(input)? as usize;
+            starts.push(previous);
+        }
+        self.line_index = LineIndex {
+            starts,
+            breaks: read_u64(input)? as usize,
+            tail: read_u64(input)? as usize,
+            size: read_u64(input)? as usize,
+        };
+        if !self.line_index.is_consistent() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "inconsistent line index",
+            ));
+        }
+        let (mut timestamp, mut line_index) = (0, 0);
+        for _ in 0..read_u64(input)? {
+            timestamp += read_i64(input)?;
+            line_index += read_i64(input)?;
+            self.records.push(Line {
+                timestamp,
+                source_file: 0,
+                index: line_index as usize,
+                line_count: read_u64(input)? as usize,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Indices of files loaded before, one entry per path in a directory like `~/.cache/logchuck`.
+#[derive(Debug)]
+pub struct IndexCache {
+    dir: PathBuf,
+    max_size: u64,
+    max_age: Duration,
+}
+
+impl IndexCache {
+    pub fn new(dir: PathBuf) -> IndexCache {
+        IndexCache {
+            dir,
+            max_size: MAX_SIZE,
+            max_age: MAX_AGE,
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/logchuck`, or `~/.cache/logchuck`.
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .map(|dir| dir.join("logchuck"))
+    }
+
+    /// The index of `path` if it still describes the file, or its start, and was parsed with `parser_key`.
+    pub fn find(&self, path: &str, parser_key: u64) -> Option<FileIndex> {
+        let (mut index, mut input) = self.header(path, parser_key)?;
+        match index.read_body(&mut input) {
+            Ok(()) => {
+                info!(
+                    "IndexCache::find - reusing index, file={}, lines={}",
+                    path,
+                    index.line_index.len()
+                );
+                Some(index)
+            }
+            Err(err) => {
+                warn!(
+                    "IndexCache::find - unreadable index, file={}, error={}",
+                    path, err
+                );
+                None
+            }
+        }
+    }
+
+    /// Earliest and latest timestamp of `path` from its index, without reading the rest of the index.
+    pub fn span(&self, path: &str, parser_key: u64) -> Option<(i64, i64)> {
+        self.header(path, parser_key).map(|(index, _)| index.span)
+    }
+
+    /// The start of the index of `path`, like `find`, and the input to read the rest of it from.
+    fn header(&self, path: &str, parser_key: u64) -> Option<(FileIndex, impl Read)> {
+        let header = fs::File::open(self.entry(path)).and_then(|file| {
+            let mut input = BufReader::new(file);
+            FileIndex::read_header(&mut input).map(|index| (index, input))
+        });
+        let (index, input) = match header {
+            Ok(header) => header,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                warn!(
+                    "IndexCache::header - unreadable index, file={}, error={}",
+                    path, err
+                );
+                return None;
+            }
+        };
+        if index.path != path || index.parser_key != parser_key {
+            info!(
+                "IndexCache::header - index for other settings, file={}",
+                path
+            );
+            return None;
+        }
+        match index.describes_file() {
+            Ok(true) => {
+                // the modification time of an entry tells when it was used last
+                let _ = fs::File::options()
+                    .write(true)
+                    .open(self.entry(path))
+                    .and_then(|entry| entry.set_modified(SystemTime::now()));
+                Some((index, input))
+            }
+            Ok(false) => {
+                info!("IndexCache::header - file changed, file={}", path);
+                None
+            }
+            Err(err) => {
+                warn!(
+                    "IndexCache::header - cannot check file={}, error={}",
+                    path, err
+                );
+                None
+            }
+        }
+    }
+
+    /// Replaces the entry of the indexed file, then removes entries beyond the size and age limits.
+    pub fn store(&self, index: &FileIndex) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let entry = self.entry(&index.path);
+        // written aside and renamed, so a concurrent load never reads half of it
+        let temp = entry.with_extension(format!("{}.tmp", std::process::id()));
+        let mut out = BufWriter::new(fs::File::create(&temp)?);
+        index.write(&mut out)?;
+        out.flush()?;
+        drop(out);
+        fs::rename(&temp, &entry)?;
+        if let Err(err) = self.prune(&entry) {
+            warn!("IndexCache::store - cannot prune, error={}", err);
+        }
+        Ok(())
+    }
+
+    /// Removes the entries not used for `max_age`, and those used longest ago beyond `max_size`.
+    fn prune(&self, kept: &Path) -> io::Result<()> {
+        let now = SystemTime::now();
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            if path.extension().is_none_or(|e| e != "index") || path == kept {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            entries.push((metadata.modified()?, metadata.len(), path));
+        }
+        entries.sort();
+
+        let mut size = fs::metadata(kept)?.len() + entries.iter().map(|e| e.1).sum::<u64>();
+        for (modified, len, path) in entries {
+            let old = now.duration_since(modified).unwrap_or_default() > self.max_age;
+            if !old && size <= self.max_size {
+                break;
+            }
+            info!("IndexCache::prune - removing entry={}", path.display());
+            fs::remove_file(&path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+
+    fn entry(&self, path: &str) -> PathBuf {
+        let mut hasher = Fnv::default();
+        hasher.write_str(path);
+        self.dir.join(format!("{:016x}.index", hasher.finish()))
+    }
+}
+
+/// Hashes the first and the last `HASH_BLOCK` bytes of the first `len` bytes of the file at `path`.
+fn content_hash(path: &str, len: u64) -> io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Fnv::default();
+    hasher.write_u64(len);
+
+    let mut block: Vec<u8> = Vec::new();
+    (&mut file)
+        .take(len.min(HASH_BLOCK))
+        .read_to_end(&mut block)?;
+    if len > HASH_BLOCK {
+        let start = (len - HASH_BLOCK).max(HASH_BLOCK);
+        file.seek(SeekFrom::Start(start))?;
+        file.take(len - start).read_to_end(&mut block)?;
+    }
+    if (block.len() as u64) < len.min(2 * HASH_BLOCK) {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "file shorter than indexed",
+        ));
+    }
+    hasher.write(&block);
+    Ok(hasher.finish())
+}
+
+/// Little endian base 128, small values take a single byte.
+fn write_u64(out: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0; 1];
+        input.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "number too long",
+    ))
+}
+
+/// Zigzag encoded, so small negative values stay small too.
+fn write_i64(out: &mut impl Write, value: i64) -> io::Result<()> {
+    write_u64(out, ((value << 1) ^ (value >> 63)) as u64)
+}
+
+fn read_i64(input: &mut impl Read) -> io::Result<i64> {
+    let value = read_u64(input)?;
+    Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+}
+
+fn read_fixed(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn write_str(out: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u64(out, value.len() as u64)?;
+    out.write_all(value.as_bytes())
+}
+
+fn read_str(input: &mut impl Read) -> io::Result<String> {
+    let len = read_u64(input)?;
+    let mut bytes: Vec<u8> = Vec::new();
+    input.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
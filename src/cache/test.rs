@@ -0,0 +1,235 @@
+use super::*;
+use crate::text::fixtures::TempDir;
+use crate::text::{modified_time, LoadProgress};
+use crate::timestamp::TimestampParser;
+use encoding_rs::UTF_8;
+use std::time::{Duration, SystemTime};
+
+/// Reads and parses the file at `path` like a load does, reusing and then updating its index in `cache`.
+fn load(
+    cache: &IndexCache,
+    path: &str,
+    parser: &TimestampParser,
+) -> (FileWithLines, Vec<Line>, bool) {
+    let parser_key = parser.fingerprint(path, modified_time(path));
+    let index = cache.find(path, parser_key);
+    let progress = LoadProgress::default();
+    let mut file = FileWithLines::read(path.to_string(), UTF_8, &progress, index.as_ref()).unwrap();
+    let (lines, out_of_order) = file
+        .get_annotated_lines_with_progress(0, parser, &progress, index.as_ref())
+        .unwrap();
+    file.set_out_of_order(out_of_order);
+    cache
+        .store(&FileIndex::new(&file, &lines, parser_key).unwrap())
+        .unwrap();
+    (file, lines, index.is_some())
+}
+
+#[test]
+fn numbers_roundtrip_test() {
+    let mut out: Vec<u8> = Vec::new();
+    let values: Vec<i64> = vec![0, 1, -1, 63, -64, 300, i64::MAX, i64::MIN];
+    for value in &values {
+        write_i64(&mut out, *value).unwrap();
+    }
+    write_u64(&mut out, u64::MAX).unwrap();
+
+    let mut input = out.as_slice();
+    for value in &values {
+        assert_eq!(read_i64(&mut input).unwrap(), *value);
+    }
+    assert_eq!(read_u64(&mut input).unwrap(), u64::MAX);
+    assert!(input.is_empty());
+}
+
+#[test]
+fn index_roundtrip_test() {
+    let index = FileIndex {
+        path: String::from("logs/app.log"),
+        plain: true,
+        file_size: 120,
+        modified: 1683109550000000,
+        hash: 42,
+        parser_key: 7,
+        detected_format: Some(String::from("ISO 8601")),
+        out_of_order: 1,
+        span: (1683109550000000, 1683109551000000),
+        line_index: LineIndex::of(&[b'\n'; 120]),
+        records: vec![
+            Line {
+                timestamp: 1683109551000000,
+                source_file: 0,
+                index: 1,
+                line_count: 2,
+            },
+            Line {
+                timestamp: 1683109550000000,
+                source_file: 0,
+                index: 0,
+                line_count: 1,
+            },
+        ],
+    };
+    let mut out: Vec<u8> = Vec::new();
+    index.write(&mut out).unwrap();
+    assert_eq!(FileIndex::read(&mut out.as_slice()).unwrap(), index);
+
+    // an entry written by another version is not read
+    out[MAGIC.len() - 2] = b'0';
+    assert!(FileIndex::read(&mut out.as_slice()).is_err());
+}
+
+#[test]
+fn appended_file_reuses_index_test() {
+    let dir = TempDir::new("cache_appended");
+    let cache = IndexCache::new(dir.path().join("cache"));
+    let path = dir.path().join("app.log").to_string_lossy().into_owned();
+    let parser = TimestampParser::default();
+    fs::write(
+        &path,
+        "2023-05-03 10:25:50 - one\n\tcontinued\n2023-05-03 10:25:52 - two\n2023-05-03 10:25",
+    )
+    .unwrap();
+
+    let (_, _, reused) = load(&cache, &path, &parser);
+    assert!(!reused);
+    let (_, lines, reused) = load(&cache, &path, &parser);
+    assert!(reused);
+    assert_eq!(lines.len(), 2);
+
+    // the unfinished line is indexed again, the record before it gets a continuation line
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b":53 - three\n\tcontinued\n2023-05-03 10:25:51 - four\n")
+        .unwrap();
+    let (file, lines, reused) = load(&cache, &path, &parser);
+    assert!(reused);
+    assert_eq!(file.out_of_order(), 1);
+
+    let fresh = FileWithLines::read(path.clone(), UTF_8, &LoadProgress::default(), None).unwrap();
+    assert_eq!(file.line_index(), fresh.line_index());
+    assert_eq!(lines, fresh.get_annotated_lines(0, &parser).unwrap());
+    assert_eq!(
+        file.get_record(&lines[3]).unwrap(),
+        "2023-05-03 10:25:53 - three\n\tcontinued"
+    );
+}
+
+#[test]
+fn changed_file_or_settings_miss_test() {
+    let dir = TempDir::new("cache_changed");
+    let cache = IndexCache::new(dir.path().join("cache"));
+    let path = dir.path().join("app.log").to_string_lossy().into_owned();
+    let parser = TimestampParser::default();
+    fs::write(
+        &path,
+        "2023-05-03 10:25:50 - one\n2023-05-03 10:25:51 - two\n",
+    )
+    .unwrap();
+
+    let key = || parser.fingerprint(&path, modified_time(&path));
+    let (_, _, reused) = load(&cache, &path, &parser);
+    assert!(!reused);
+    assert!(cache.find(&path, key()).is_some());
+    assert!(cache.find(&path, key() + 1).is_none());
+    assert_eq!(
+        cache.span(&path, key()),
+        Some((1683109550000000, 1683109551000000))
+    );
+
+    // rewritten with other content of the same size
+    fs::write(
+        &path,
+        "2023-05-03 11:25:50 - one\n2023-05-03 11:25:51 - two\n",
+    )
+    .unwrap();
+    assert!(cache.find(&path, key()).is_none());
+
+    // truncated
+    fs::write(&path, "2023-05-03 12:25:50 - one\n").unwrap();
+    assert!(cache.find(&path, key()).is_none());
+}
+
+#[test]
+fn year_of_modification_time_test() {
+    let dir = TempDir::new("cache_year");
+    let cache = IndexCache::new(dir.path().join("cache"));
+    let path = dir.write("syslog", "May  3 10:25:50 host app: one\n");
+    let path = path.to_string_lossy().into_owned();
+    let parser = TimestampParser::default();
+    let modified = |secs: u64| {
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    };
+
+    // 2023-06-01
+    modified(1685577600);
+    let (_, lines, _) = load(&cache, &path, &parser);
+    assert_eq!(lines[0].timestamp, 1683109550000000);
+    // touched a year later, syslog timestamps take their year from it
+    modified(1717200000);
+    let (_, lines, reused) = load(&cache, &path, &parser);
+    assert!(!reused);
+    assert_eq!(lines[0].timestamp, 1714731950000000);
+}
+
+#[test]
+fn fnv_test() {
+    // the hashes are stored, they must not change
+    assert_eq!(Fnv::default().finish(), 0xcbf2_9ce4_8422_2325);
+    let mut hasher = Fnv::default();
+    hasher.write(b"a");
+    assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(
+        IndexCache::new(PathBuf::from("cache")).entry("logs/app.log"),
+        PathBuf::from("cache/e5d763cdc36700f6.index")
+    );
+}
+
+#[test]
+fn store_prunes_entries_test() {
+    let dir = TempDir::new("cache_prune");
+    let parser = TimestampParser::default();
+    let mut paths: Vec<String> = Vec::new();
+    // names of the same length, their entries have the same size
+    for name in ["one.log", "two.log", "six.log"] {
+        let path = dir.write(name, "2023-05-03 10:25:50 - one\n");
+        paths.push(path.to_string_lossy().into_owned());
+    }
+    let cache = IndexCache::new(dir.path().join("cache"));
+    let used_ago = |path: &str, days: u64| {
+        let entry = fs::File::options()
+            .write(true)
+            .open(cache.entry(path))
+            .unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60);
+        entry.set_modified(modified).unwrap();
+    };
+    let kept = |cache: &IndexCache| -> Vec<bool> {
+        paths
+            .iter()
+            .map(|path| {
+                cache
+                    .find(path, parser.fingerprint(path, modified_time(path)))
+                    .is_some()
+            })
+            .collect()
+    };
+
+    load(&cache, &paths[0], &parser);
+    used_ago(&paths[0], 31);
+    load(&cache, &paths[1], &parser);
+    assert_eq!(kept(&cache), vec![false, true, false]);
+
+    // room for two entries, the one used longest ago goes
+    let entry_size = fs::metadata(cache.entry(&paths[1])).unwrap().len();
+    let cache = IndexCache {
+        max_size: 2 * entry_size,
+        ..IndexCache::new(dir.path().join("cache"))
+    };
+    load(&cache, &paths[0], &parser);
+    used_ago(&paths[0], 2);
+    used_ago(&paths[1], 1);
+    load(&cache, &paths[2], &parser);
+    assert_eq!(kept(&cache), vec![false, true, true]);
+}
@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod test;
+
+use crate::export::write_lines;
+use crate::filter::Filter;
+use crate::load::Loader;
+use crate::mergeline::Line;
+use crate::scan::{loadable_files, scan_directory, ScanOptions};
+use crate::text::{unique_names, FileWithLines};
+use crate::timestamp::{format_timestamp, TimestampParser};
+use regex::Regex;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Merges `files` into `output`, or to stdout when no output is given.
+pub async fn merge(
+    files: Vec<String>,
+    output: Option<PathBuf>,
+    prefix_filename: bool,
+    filter: &Filter,
+    parser: TimestampParser,
+) -> io::Result<()> {
+    let (files, lines) = load_files(files, parser).await?;
+    let lines = filter_lines(&files, &lines, filter);
+    match output {
+        Some(path) => write_lines(
+            &mut BufWriter::new(File::create(path)?),
+            &files,
+            &lines,
+            prefix_filename,
+        ),
+        None => write_lines(&mut io::stdout().lock(), &files, &lines, prefix_filename),
+    }
+}
+
+/// Prints the records of all files in `directory` matching `pattern`, in merged order.
+pub async fn grep(
+    pattern: &str,
+    directory: &Path,
+    filter: &Filter,
+    parser: TimestampParser,
+    scan: &ScanOptions,
+) -> io::Result<()> {
+    let regex = Regex::new(pattern).map_err(io::Error::other)?;
+    let (files, lines) =
+        load_files(loadable_files(scan_directory(directory, scan)?), parser).await?;
+    let lines = filter_lines(&files, &lines, filter);
+    write_matching(&mut io::stdout().lock(), &regex, &files, &lines)
+}
+
+/// Prints line counts and time spans of all files in `directory`.
+pub async fn stats(
+    directory: &Path,
+    parser: TimestampParser,
+    scan: &ScanOptions,
+) -> io::Result<()> {
+    let (files, lines) =
+        load_files(loadable_files(scan_directory(directory, scan)?), parser).await?;
+    write_stats(&mut io::stdout().lock(), &files, &lines)
+}
+
+/// Loads and merges `filenames` like the UI does, each file which cannot be loaded is reported on stderr.
+/// Fails only when none of them can be loaded.
+async fn load_files(
+    filenames: Vec<String>,
+    parser: TimestampParser,
+) -> io::Result<(Vec<FileWithLines>, Vec<Line>)> {
+    let count = filenames.len();
+    let loaded = Loader::start(filenames, Arc::new(parser), None)
+        .result()
+        .await;
+    for err in &loaded.failed {
+        eprintln!("cannot load {}", err);
+    }
+    if loaded.files.is_empty() && count > 0 {
+        return Err(io::Error::other(format!(
+            "none of {} files could be loaded",
+            count
+        )));
+    }
+    Ok((loaded.files, loaded.lines))
+}
+
+fn filter_lines(files: &[FileWithLines], lines: &[Line], filter: &Filter) -> Vec<Line> {
+    filter
+        .apply(files, lines)
+        .into_iter()
+        .map(|i| lines[i].clone())
+        .collect()
+}
+
+fn write_matching<W: Write>(
+    out: &mut W,
+    regex: &Regex,
+    files: &[FileWithLines],
+    lines: &[Line],
+) -> io::Result<()> {
+    let matching: Vec<Line> = lines
+        .iter()
+        .filter(|l| match files[l.source_file].get_record(l) {
+            Ok(record) => regex.is_match(&record),
+            Err(_) => false,
+        })
+        .cloned()
+        .collect();
+    write_lines(out, files, &matching, true)
+}
+
+/// One row per file of the merged `lines`, sorted by filename.
+fn write_stats<W: Write>(out: &mut W, files: &[FileWithLines], lines: &[Line]) -> io::Result<()> {
+    // record count, first and last timestamp
+    let mut spans: Vec<(usize, i64, i64)> = vec![(0, i64::MAX, i64::MIN); files.len()];
+    for line in lines {
+        let (count, first, last) = &mut spans[line.source_file];
+        *count += 1;
+        *first = (*first).min(line.timestamp);
+        *last = (*last).max(line.timestamp);
+    }
+    let filenames: Vec<String> = files.iter().map(|f| f.filename()).collect();
+    let names = unique_names(&filenames);
+    let mut sorted: Vec<usize> = (0..files.len()).collect();
+    sorted.sort_by_key(|i| &filenames[*i]);
+
+    let mut total_lines: usize = 0;
+    for i in sorted {
+        let file = &files[i];
+        let (records, first, last) = spans[i];
+        total_lines += file.len();
+        writeln!(
+            out,
+            "{}\tlines={}\trecords={}\tout_of_order={}\tformat={}\tfirst={}\tlast={}",
+            names[i],
+            file.len(),
+            records,
+            file.out_of_order(),
+            file.detected_format().unwrap_or_default(),
+            format_timestamp(first),
+            format_timestamp(last)
+        )?;
+    }
+
+    writeln!(
+        out,
+        "total\tfiles={}\tlines={}\trecords={}",
+        files.len(),
+        total_lines,
+        lines.len()
+    )
+}
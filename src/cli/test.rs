@@ -0,0 +1,56 @@
+use super::*;
+use crate::text::fixtures::{generate_files, TempDir};
+use crate::text::merge_files;
+
+#[test]
+fn grep_prints_matching_records() {
+    let files = generate_files();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let mut out: Vec<u8> = Vec::new();
+    write_matching(&mut out, &Regex::new("session=1").unwrap(), &files, &lines).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "one.log:2023-05-03 10:25:50.000000 INFO  - session=1 request\r\ntwo.log:2023-05-03 10:25:51.000000 WARN  - session=1 slow\ntwo.log:no timestamp\n"
+    );
+}
+
+#[test]
+fn stats_per_file() {
+    let files = generate_files();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let mut out: Vec<u8> = Vec::new();
+    write_stats(&mut out, &files, &lines).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "one.log\tlines=3\trecords=2\tout_of_order=0\tformat=ISO 8601\tfirst=2023-05-03 10:25:50.000000\tlast=2023-05-03 10:25:52.000000\n\
+         two.log\tlines=3\trecords=2\tout_of_order=0\tformat=ISO 8601\tfirst=2023-05-03 10:25:51.000000\tlast=2023-05-03 10:25:53.000000\n\
+         total\tfiles=2\tlines=6\trecords=4\n"
+    );
+}
+
+#[tokio::test]
+async fn files_failing_to_load_are_left_out() {
+    let dir = TempDir::new("cli_failing");
+    let good = dir.write("good.log", "2023-05-03 10:25:50 - one\n");
+    let untimed = dir.write("untimed.log", "no timestamp\n");
+    let missing = dir.path().join("missing.log");
+    let filenames: Vec<String> = [&good, &untimed, &missing]
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+
+    let (files, lines) = load_files(filenames.clone(), TimestampParser::default())
+        .await
+        .unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].filename(), filenames[0]);
+    assert_eq!(lines.len(), 1);
+
+    assert!(
+        load_files(filenames[1..].to_vec(), TimestampParser::default())
+            .await
+            .is_err()
+    );
+}
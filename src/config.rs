@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod test;
+
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Read from the working directory like `config/log4rs.yaml`, may be missing.
+pub const DEFAULT_CONFIG: &str = "config/logchuck.yaml";
+
+/// Settings from the configuration file, every section is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Tried before the built-in timestamp formats.
+    pub timestamp_formats: Vec<FormatConfig>,
+    /// Timezones and clock skews of files, the first matching entry applies.
+    pub time_corrections: Vec<CorrectionConfig>,
+    /// Text encodings of files which are not UTF-8, the first matching entry applies.
+    pub encodings: Vec<EncodingConfig>,
+    /// Where the line breaks and timestamps of loaded files are kept, defaults to `~/.cache/logchuck`.
+    pub cache_dir: Option<PathBuf>,
+    /// Which files of the scanned directory and its subdirectories are listed.
+    pub scan: ScanConfig,
+}
+
+/// Extended by the options given on the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanConfig {
+    /// Levels of subdirectories to descend into, 0 by default.
+    pub depth: Option<usize>,
+    /// Globs of the files to list, e.g. `*.log` or `host*/app/*.log`, all files if empty.
+    pub include: Vec<String>,
+    /// Globs of the files and directories to leave out, e.g. `archive`.
+    pub exclude: Vec<String>,
+}
+
+/// A user-defined timestamp format, e.g. for `03.05.2023 10:25:50:262`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FormatConfig {
+    /// strftime pattern like `%d.%m.%Y %H:%M:%S:%3f`, timestamps without `%z` are in the timezone of the file.
+    pub pattern: String,
+    /// Locates the timestamp in the line, its first group or else the whole match is parsed.
+    pub regex: Option<String>,
+    /// Glob on the filename, e.g. `device*.log`, the format is only tried on matching files.
+    pub files: Option<String>,
+}
+
+/// Puts the timestamps of some files on the common clock, e.g. for devices logging local time.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorrectionConfig {
+    /// Glob on the filename, e.g. `device*.log`.
+    pub files: String,
+    /// Timezone of timestamps without an offset, e.g. `+02:00`.
+    pub timezone: Option<String>,
+    /// Seconds added to every timestamp, e.g. `2.35` for a clock running behind.
+    pub skew: Option<f64>,
+}
+
+/// Reads some files in another encoding than UTF-8, e.g. Latin-1 device logs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncodingConfig {
+    /// Glob on the filename, e.g. `device*.log`.
+    pub files: String,
+    /// Label like `latin1`, `windows-1252` or `utf-16le`, a byte order mark in the file takes precedence.
+    pub encoding: String,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, io::Error> {
+        // the YAML parser refuses a document without any value
+        let is_blank = |line: &str| line.trim().is_empty() || line.trim().starts_with('#');
+        if text.lines().all(is_blank) {
+            return Ok(Config::default());
+        }
+        serde_yaml::from_str(text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Reads `path`, a missing file is only an error when `required`.
+    pub fn load(path: &Path, required: bool) -> Result<Config, io::Error> {
+        let result = match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text),
+            Err(err) if !required && err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(err) => Err(err),
+        };
+        result.map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("cannot read config={}, error={}", path.display(), err),
+            )
+        })
+    }
+}
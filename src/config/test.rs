@@ -0,0 +1,95 @@
+use super::*;
+
+#[test]
+fn parse_formats_test() {
+    let config = Config::parse(
+        r#"
+timestamp_formats:
+  - pattern: "%d.%m.%Y %H:%M:%S:%3f"
+  - pattern: "%H:%M:%S"
+    regex: 'at (\d{2}:\d{2}:\d{2})'
+    files: "device*.log"
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.timestamp_formats.len(), 2);
+    assert_eq!(config.timestamp_formats[0].pattern, "%d.%m.%Y %H:%M:%S:%3f");
+    assert!(config.timestamp_formats[0].regex.is_none());
+    assert_eq!(
+        config.timestamp_formats[1].files.as_deref(),
+        Some("device*.log")
+    );
+}
+
+#[test]
+fn parse_empty_test() {
+    assert!(Config::parse("").unwrap().timestamp_formats.is_empty());
+    assert!(Config::parse("# nothing yet\n")
+        .unwrap()
+        .timestamp_formats
+        .is_empty());
+    assert!(Config::parse("timestamp_format: []").is_err());
+}
+
+#[test]
+fn load_missing_test() {
+    let path = Path::new("does/not/exist.yaml");
+    assert!(Config::load(path, false).is_ok());
+    assert!(Config::load(path, true).is_err());
+}
+
+#[test]
+fn parse_corrections_test() {
+    let config = Config::parse(
+        r#"
+time_corrections:
+  - files: "device*.log"
+    timezone: "+02:00"
+    skew: 2.35
+  - files: "gateway.log"
+    skew: -0.5
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.time_corrections.len(), 2);
+    assert_eq!(
+        config.time_corrections[0].timezone.as_deref(),
+        Some("+02:00")
+    );
+    assert_eq!(config.time_corrections[0].skew, Some(2.35));
+    assert!(config.time_corrections[1].timezone.is_none());
+}
+
+#[test]
+fn parse_encodings_test() {
+    let config = Config::parse(
+        r#"
+encodings:
+  - files: "device*.log"
+    encoding: "latin1"
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.encodings.len(), 1);
+    assert_eq!(config.encodings[0].files, "device*.log");
+    assert_eq!(config.encodings[0].encoding, "latin1");
+}
+
+#[test]
+fn parse_scan_test() {
+    let config = Config::parse(
+        r#"
+scan:
+  depth: 2
+  exclude: ["archive", "*.tmp"]
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.scan.depth, Some(2));
+    assert!(config.scan.include.is_empty());
+    assert_eq!(config.scan.exclude, vec!["archive", "*.tmp"]);
+}
@@ -0,0 +1,460 @@
+#[cfg(test)]
+mod test;
+
+use log::*;
+use std::fs;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+/// Separates the path of an archive from the name of a member inside of it, e.g. `bundle.zip!/app/one.log`.
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+const TAR_MAGIC: &[u8; 5] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+const HEADER_LEN: usize = TAR_MAGIC_OFFSET + TAR_MAGIC.len();
+
+/// A compression format which wraps a single stream, e.g. gzip.
+pub trait Decompressor: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn matches(&self, header: &[u8]) -> bool;
+    fn reader<'a>(&self, input: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>>;
+}
+
+/// Input which can be read out of order, e.g. a file on disk.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// A container format holding several files, e.g. tar or zip.
+pub trait ArchiveFormat: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn matches(&self, header: &[u8]) -> bool;
+    /// Lists the regular files stored in the archive.
+    fn members(&self, input: Box<dyn Read + '_>) -> io::Result<Vec<Member>>;
+    /// Extracts one member, or all members concatenated when `member` is `None`.
+    fn extract(&self, input: Box<dyn Read + '_>, member: Option<&str>) -> io::Result<Vec<u8>>;
+    /// Like `members`, formats keeping an index may skip to it.
+    fn members_seekable(&self, input: Box<dyn ReadSeek + '_>) -> io::Result<Vec<Member>> {
+        self.members(input)
+    }
+    /// Like `extract`, formats keeping an index may skip to the member.
+    fn extract_seekable(
+        &self,
+        input: Box<dyn ReadSeek + '_>,
+        member: Option<&str>,
+    ) -> io::Result<Vec<u8>> {
+        self.extract(input, member)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: String,
+    pub size: u64,
+}
+
+/// The decompressed stream together with the archive format found inside of it.
+type Unwrapped<'r, 'a> = (Box<dyn Read + 'a>, Option<&'r dyn ArchiveFormat>);
+
+pub struct Registry {
+    decompressors: Vec<Box<dyn Decompressor>>,
+    archives: Vec<Box<dyn ArchiveFormat>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::empty();
+        registry.register_decompressor(Box::new(Gzip));
+        registry.register_decompressor(Box::new(Zstd));
+        registry.register_decompressor(Box::new(Xz));
+        registry.register_decompressor(Box::new(Bzip2));
+        registry.register_archive(Box::new(Tar));
+        registry.register_archive(Box::new(Zip));
+        registry
+    }
+}
+
+impl Registry {
+    pub fn empty() -> Registry {
+        Registry {
+            decompressors: Vec::new(),
+            archives: Vec::new(),
+        }
+    }
+
+    pub fn register_decompressor(&mut self, decompressor: Box<dyn Decompressor>) {
+        self.decompressors.push(decompressor);
+    }
+
+    pub fn register_archive(&mut self, archive: Box<dyn ArchiveFormat>) {
+        self.archives.push(archive);
+    }
+
+    /// Lists the members of the archive at `path`, returns an empty vector if it is not an archive.
+    pub fn list_members(&self, path: &str) -> io::Result<Vec<Member>> {
+        self.members_seekable(Box::new(fs::File::open(path)?))
+    }
+
+    /// Whether `path` is a file read as it is, neither compressed nor an archive or a member of one.
+    pub fn is_plain(&self, path: &str) -> io::Result<bool> {
+        if split_member(path).1.is_some() {
+            return Ok(false);
+        }
+        let (header, _) = peek(Box::new(fs::File::open(path)?))?;
+        Ok(!self.decompressors.iter().any(|d| d.matches(&header))
+            && !self.archives.iter().any(|a| a.matches(&header)))
+    }
+
+    /// The first `len` bytes of the decompressed file at `path`, none for an archive or a member of one.
+    pub fn head(&self, path: &str, len: usize) -> io::Result<Option<Vec<u8>>> {
+        if split_member(path).1.is_some() {
+            return Ok(None);
+        }
+        match self.unwrap_stream(Box::new(fs::File::open(path)?))? {
+            (input, None) => {
+                let mut head: Vec<u8> = Vec::new();
+                input.take(len as u64).read_to_end(&mut head)?;
+                Ok(Some(head))
+            }
+            (_, Some(_)) => Ok(None),
+        }
+    }
+
+    /// Decompresses `input`, or extracts `member` if it is an archive.
+    pub fn decompress<'a>(
+        &self,
+        input: Box<dyn Read + 'a>,
+        member: Option<&str>,
+    ) -> io::Result<Vec<u8>> {
+        let (input, archive) = self.unwrap_stream(input)?;
+        match (archive, member) {
+            (Some(archive), _) => archive.extract(input, member),
+            (None, None) => {
+                let mut result: Vec<u8> = Vec::new();
+                let mut input = input;
+                input.read_to_end(&mut result)?;
+                Ok(result)
+            }
+            (None, Some(member)) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("not an archive, cannot read member={}", member),
+            )),
+        }
+    }
+
+    pub fn members<'a>(&self, input: Box<dyn Read + 'a>) -> io::Result<Vec<Member>> {
+        match self.unwrap_stream(input)? {
+            (input, Some(archive)) => archive.members(input),
+            (_, None) => Ok(Vec::new()),
+        }
+    }
+
+    /// Like `decompress`, an uncompressed archive is read only where its format needs to.
+    pub fn decompress_seekable<'a>(
+        &self,
+        mut input: Box<dyn ReadSeek + 'a>,
+        member: Option<&str>,
+    ) -> io::Result<Vec<u8>> {
+        match self.seekable_archive(input.as_mut())? {
+            Some(archive) => archive.extract_seekable(input, member),
+            None => self.decompress(input, member),
+        }
+    }
+
+    /// Like `members`, an uncompressed archive is read only where its format needs to.
+    pub fn members_seekable<'a>(
+        &self,
+        mut input: Box<dyn ReadSeek + 'a>,
+    ) -> io::Result<Vec<Member>> {
+        match self.seekable_archive(input.as_mut())? {
+            Some(archive) => archive.members_seekable(input),
+            None => self.members(input),
+        }
+    }
+
+    /// The archive format of `input` if it is not compressed, `input` is left where it was.
+    fn seekable_archive(&self, input: &mut dyn ReadSeek) -> io::Result<Option<&dyn ArchiveFormat>> {
+        let start = input.stream_position()?;
+        let mut header: Vec<u8> = Vec::new();
+        (&mut *input)
+            .take(HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        input.seek(SeekFrom::Start(start))?;
+        if self.decompressors.iter().any(|d| d.matches(&header)) {
+            return Ok(None);
+        }
+        Ok(self
+            .archives
+            .iter()
+            .find(|a| a.matches(&header))
+            .map(|a| a.as_ref()))
+    }
+
+    /// Strips the stream compression, if any, and detects the archive format of what is left.
+    fn unwrap_stream<'a>(&self, input: Box<dyn Read + 'a>) -> io::Result<Unwrapped<'_, 'a>> {
+        let (header, mut input) = peek(input)?;
+        if let Some(decompressor) = self.decompressors.iter().find(|d| d.matches(&header)) {
+            debug!(
+                "Registry::unwrap_stream - decompressing {}",
+                decompressor.name()
+            );
+            input = decompressor.reader(input)?;
+        }
+
+        let (header, input) = peek(input)?;
+        let archive = self
+            .archives
+            .iter()
+            .find(|a| a.matches(&header))
+            .map(|a| a.as_ref());
+        if let Some(archive) = archive {
+            debug!("Registry::unwrap_stream - found {} archive", archive.name());
+        }
+        Ok((input, archive))
+    }
+}
+
+pub fn list_members(path: &str) -> io::Result<Vec<Member>> {
+    Registry::default().list_members(path)
+}
+
+pub fn is_plain(path: &str) -> io::Result<bool> {
+    Registry::default().is_plain(path)
+}
+
+pub fn head(path: &str, len: usize) -> io::Result<Option<Vec<u8>>> {
+    Registry::default().head(path, len)
+}
+
+pub fn split_member(path: &str) -> (&str, Option<&str>) {
+    match path.split_once(MEMBER_SEPARATOR) {
+        Some((archive, member)) => (archive, Some(member)),
+        None => (path, None),
+    }
+}
+
+pub fn member_path(archive: &str, member: &str) -> String {
+    format!("{}{}{}", archive, MEMBER_SEPARATOR, member)
+}
+
+/// Reads the first few bytes of `input` without consuming them, detection is done by magic bytes only.
+fn peek<'a>(mut input: Box<dyn Read + 'a>) -> io::Result<(Vec<u8>, Box<dyn Read + 'a>)> {
+    let mut header: Vec<u8> = Vec::new();
+    (&mut input)
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    let chained: Box<dyn Read + 'a> = Box::new(Cursor::new(header.clone()).chain(input));
+    Ok((header, chained))
+}
+
+fn append_line(result: &mut Vec<u8>, input: &mut dyn Read) -> io::Result<()> {
+    input.read_to_end(result)?;
+    if !result.is_empty() && !result.ends_with(b"\n") {
+        result.push(b'\n');
+    }
+    Ok(())
+}
+
+struct Gzip;
+
+impl Decompressor for Gzip {
+    fn name(&self) -> &'static str {
+        "gzip"
+    }
+
+    fn matches(&self, header: &[u8]) -> bool {
+        header.starts_with(&[0x1f, 0x8b])
+    }
+
+    fn reader<'a>(&self, input: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(flate2::read::MultiGzDecoder::new(input)))
+    }
+}
+
+struct Zstd;
+
+impl Decompressor for Zstd {
+    fn name(&self) -> &'static str {
+        "zstd"
+    }
+
+    fn matches(&self, header: &[u8]) -> bool {
+        header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd])
+    }
+
+    fn reader<'a>(&self, input: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(zstd::stream::read::Decoder::new(input)?))
+    }
+}
+
+struct Xz;
+
+impl Decompressor for Xz {
+    fn name(&self) -> &'static str {
+        "xz"
+    }
+
+    fn matches(&self, header: &[u8]) -> bool {
+        header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00])
+    }
+
+    fn reader<'a>(&self, input: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(xz2::read::XzDecoder::new_multi_decoder(input)))
+    }
+}
+
+struct Bzip2;
+
+impl Decompressor for Bzip2 {
+    fn name(&self) -> &'static str {
+        "bzip2"
+    }
+
+    fn matches(&self, header: &[u8]) -> bool {
+        header.starts_with(b"BZh")
+    }
+
+    fn reader<'a>(&self, input: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(bzip2::read::MultiBzDecoder::new(input)))
+    }
+}
+
+struct Tar;
+
+impl ArchiveFormat for Tar {
+    fn name(&self) -> &'static str {
+        "tar"
+    }
+
+    fn matches(&self, header: &[u8]) -> bool {
+        header.len() >= HEADER_LEN && &header[TAR_MAGIC_OFFSET..HEADER_LEN] == TAR_MAGIC
+    }
+
+    fn members(&self, input: Box<dyn Read + '_>) -> io::Result<Vec<Member>> {
+        let mut result: Vec<Member> = Vec::new();
+        let mut archive = tar::Archive::new(input);
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            result.push(Member {
+                name: entry.path()?.to_string_lossy().to_string(),
+                size: entry.size(),
+            });
+        }
+        Ok(result)
+    }
+
+    fn extract(&self, input: Box<dyn Read + '_>, member: Option<&str>) -> io::Result<Vec<u8>> {
+        let mut result: Vec<u8> = Vec::new();
+        let mut archive = tar::Archive::new(input);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            match member {
+                None => append_line(&mut result, &mut entry)?,
+                Some(name) => {
+                    if entry.path()?.to_string_lossy() == name {
+                        entry.read_to_end(&mut result)?;
+                        return Ok(result);
+                    }
+                }
+            }
+        }
+
+        match member {
+            None => Ok(result),
+            Some(name) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such member in tar archive, member={}", name),
+            )),
+        }
+    }
+}
+
+struct Zip;
+
+impl Zip {
+    /// Zip keeps its index at the end of the file, an archive which cannot seek there has to be in memory.
+    fn buffer<'a>(mut input: Box<dyn Read + 'a>) -> io::Result<Box<dyn ReadSeek + 'a>> {
+        let mut bytes: Vec<u8> = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        Ok(Box::new(Cursor::new(bytes)))
+    }
+
+    fn open<'a>(
+        input: Box<dyn ReadSeek + 'a>,
+    ) -> io::Result<zip::ZipArchive<Box<dyn ReadSeek + 'a>>> {
+        zip::ZipArchive::new(input).map_err(invalid_zip)
+    }
+}
+
+/// Errors of the zip crate are about the archive itself, not about reading it.
+fn invalid_zip(err: zip::result::ZipError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+impl ArchiveFormat for Zip {
+    fn name(&self) -> &'static str {
+        "zip"
+    }
+
+    fn matches(&self, header: &[u8]) -> bool {
+        header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06")
+    }
+
+    fn members(&self, input: Box<dyn Read + '_>) -> io::Result<Vec<Member>> {
+        self.members_seekable(Zip::buffer(input)?)
+    }
+
+    fn extract(&self, input: Box<dyn Read + '_>, member: Option<&str>) -> io::Result<Vec<u8>> {
+        self.extract_seekable(Zip::buffer(input)?, member)
+    }
+
+    fn members_seekable(&self, input: Box<dyn ReadSeek + '_>) -> io::Result<Vec<Member>> {
+        let mut archive = Zip::open(input)?;
+        let mut result: Vec<Member> = Vec::new();
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).map_err(invalid_zip)?;
+            if !file.is_file() {
+                continue;
+            }
+            result.push(Member {
+                name: file.name().to_string(),
+                size: file.size(),
+            });
+        }
+        Ok(result)
+    }
+
+    fn extract_seekable(
+        &self,
+        input: Box<dyn ReadSeek + '_>,
+        member: Option<&str>,
+    ) -> io::Result<Vec<u8>> {
+        let mut archive = Zip::open(input)?;
+        let mut result: Vec<u8> = Vec::new();
+        match member {
+            Some(name) => {
+                let mut file = archive.by_name(name).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no such member in zip archive, member={}", name),
+                    )
+                })?;
+                file.read_to_end(&mut result)?;
+            }
+            None => {
+                for i in 0..archive.len() {
+                    let mut file = archive.by_index(i).map_err(invalid_zip)?;
+                    if file.is_file() {
+                        append_line(&mut result, &mut file)?;
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+}
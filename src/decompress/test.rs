@@ -0,0 +1,190 @@
+use super::*;
+use std::io::Write;
+
+const ONE: &[u8] = b"2023-05-03 10:25:50.262116 - one\n";
+const TWO: &[u8] = b"2023-05-03 10:25:51.262116 - two\n";
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn zstd(bytes: &[u8]) -> Vec<u8> {
+    zstd::stream::encode_all(bytes, 0).unwrap()
+}
+
+fn xz(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn bzip2(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn tar(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, data) in members {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, *data).unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+fn zip(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in members {
+        writer
+            .start_file(*name, zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn decompress(bytes: &[u8], member: Option<&str>) -> io::Result<Vec<u8>> {
+    Registry::default().decompress(Box::new(bytes), member)
+}
+
+fn members(bytes: &[u8]) -> Vec<Member> {
+    Registry::default().members(Box::new(bytes)).unwrap()
+}
+
+#[test]
+fn plain_text_is_untouched() {
+    assert_eq!(decompress(ONE, None).unwrap(), ONE.to_vec());
+    assert!(members(ONE).is_empty());
+}
+
+#[test]
+fn stream_formats_are_decompressed() {
+    let text = [ONE, TWO].concat();
+    for compressed in [gzip(&text), zstd(&text), xz(&text), bzip2(&text)] {
+        assert_eq!(decompress(&compressed, None).unwrap(), text);
+        assert!(members(&compressed).is_empty());
+    }
+}
+
+#[test]
+fn tar_members_are_concatenated() {
+    let archive = tar(&[("one.log", &ONE[..ONE.len() - 1]), ("two.log", TWO)]);
+    for compressed in [
+        gzip(&archive),
+        zstd(&archive),
+        xz(&archive),
+        archive.clone(),
+    ] {
+        assert_eq!(decompress(&compressed, None).unwrap(), [ONE, TWO].concat());
+    }
+}
+
+#[test]
+fn tar_member_is_extracted() {
+    let archive = gzip(&tar(&[("one.log", ONE), ("app/two.log", TWO)]));
+    assert_eq!(
+        members(&archive),
+        vec![
+            Member {
+                name: String::from("one.log"),
+                size: ONE.len() as u64
+            },
+            Member {
+                name: String::from("app/two.log"),
+                size: TWO.len() as u64
+            }
+        ]
+    );
+    assert_eq!(
+        decompress(&archive, Some("app/two.log")).unwrap(),
+        TWO.to_vec()
+    );
+    assert!(decompress(&archive, Some("three.log")).is_err());
+}
+
+#[test]
+fn zip_member_is_extracted() {
+    let archive = zip(&[("one.log", ONE), ("app/two.log", TWO)]);
+    assert_eq!(members(&archive).len(), 2);
+    assert_eq!(decompress(&archive, Some("one.log")).unwrap(), ONE.to_vec());
+    assert_eq!(decompress(&archive, None).unwrap(), [ONE, TWO].concat());
+    assert!(decompress(&archive, Some("three.log")).is_err());
+}
+
+/// Counts the bytes read from `input`.
+struct Counting<R> {
+    input: R,
+    read: usize,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.input.read(buf)?;
+        self.read += read;
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for Counting<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.input.seek(pos)
+    }
+}
+
+#[test]
+fn seekable_zip_is_read_where_needed() {
+    // stored, so the archive is as large as the member
+    let big = ONE.repeat(32 * 1024);
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let stored =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, data) in [("big.log", &big[..]), ("two.log", TWO)] {
+        writer.start_file(name, stored).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    let archive = writer.finish().unwrap().into_inner();
+
+    let mut input = Counting {
+        input: Cursor::new(&archive),
+        read: 0,
+    };
+    let listed = Registry::default()
+        .members_seekable(Box::new(&mut input))
+        .unwrap();
+    assert_eq!(listed.len(), 2);
+    assert!(input.read < 4096, "read={}", input.read);
+
+    let mut input = Counting {
+        input: Cursor::new(&archive),
+        read: 0,
+    };
+    let two = Registry::default()
+        .decompress_seekable(Box::new(&mut input), Some("two.log"))
+        .unwrap();
+    assert_eq!(two, TWO.to_vec());
+    assert!(input.read < 4096, "read={}", input.read);
+}
+
+#[test]
+fn corrupted_gzip_is_an_error() {
+    let mut compressed = gzip(ONE);
+    compressed.truncate(12);
+    assert!(decompress(&compressed, None).is_err());
+}
+
+#[test]
+fn member_path_roundtrip() {
+    let path = member_path("/logs/bundle.zip", "app/one.log");
+    assert_eq!(path, "/logs/bundle.zip!/app/one.log");
+    assert_eq!(
+        split_member(&path),
+        ("/logs/bundle.zip", Some("app/one.log"))
+    );
+    assert_eq!(split_member("/logs/one.log"), ("/logs/one.log", None));
+}
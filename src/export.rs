@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod test;
+
+use crate::mergeline::Line;
+use crate::text::{unique_names, FileWithLines};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Name of the file exports go to by default, left out of scans so an export is not merged again.
+pub const EXPORT_FILENAME: &str = "all.log";
+
+/// Writes the records in `lines` to `out` in UTF-8, continuation lines included, whatever the encoding of their files.
+/// With `prefix_filename`, every line is prefixed with the name of its source file, like `grep` does.
+/// Byte order marks are left out, bytes invalid in the encoding of their file are written as `�`.
+pub fn write_lines<W: Write>(
+    out: &mut W,
+    files: &[FileWithLines],
+    lines: &[Line],
+    prefix_filename: bool,
+) -> io::Result<()> {
+    let filenames: Vec<String> = files.iter().map(|f| f.filename()).collect();
+    let prefixes = unique_names(&filenames);
+
+    for line in lines {
+        let record = files[line.source_file]
+            .get_record(line)
+            .map_err(|err| io::Error::other(err.error_message))?;
+        if prefix_filename {
+            for physical_line in record.split('\n') {
+                writeln!(out, "{}:{}", prefixes[line.source_file], physical_line)?;
+            }
+        } else {
+            writeln!(out, "{}", record)?;
+        }
+    }
+
+    out.flush()
+}
+
+pub fn export_to_file(
+    path: &Path,
+    files: &[FileWithLines],
+    lines: &[Line],
+    prefix_filename: bool,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_lines(&mut out, files, lines, prefix_filename)
+}
@@ -0,0 +1,137 @@
+use super::*;
+use crate::text::fixtures::{generate_files, TempDir};
+use crate::text::{merge_files, LoadProgress};
+use crate::timestamp::TimestampParser;
+use encoding_rs::{Encoding, UTF_8};
+
+#[test]
+fn export_keeps_lines_intact() {
+    let files = generate_files();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let mut out: Vec<u8> = Vec::new();
+    write_lines(&mut out, &files, &lines, false).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "2023-05-03 10:25:50.000000 INFO  - session=1 request\r\n\
+         2023-05-03 10:25:51.000000 WARN  - session=1 slow\nno timestamp\n\
+         2023-05-03 10:25:52.000000 ERROR - session=2 failed\n\tat Main.main(Main.java:3)\n\
+         2023-05-03 10:25:53.000000 - session=2 no level\n"
+    );
+}
+
+#[test]
+fn export_with_filename_prefix() {
+    let files = generate_files();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let mut out: Vec<u8> = Vec::new();
+    write_lines(&mut out, &files, &lines, true).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "one.log:2023-05-03 10:25:50.000000 INFO  - session=1 request\r\n\
+         two.log:2023-05-03 10:25:51.000000 WARN  - session=1 slow\ntwo.log:no timestamp\n\
+         one.log:2023-05-03 10:25:52.000000 ERROR - session=2 failed\none.log:\tat Main.main(Main.java:3)\n\
+         two.log:2023-05-03 10:25:53.000000 - session=2 no level\n"
+    );
+}
+
+/// Reads and parses the files written into `dir` like a load does.
+fn read_files(dir: &TempDir, files: &[(&str, &[u8], &'static Encoding)]) -> Vec<FileWithLines> {
+    files
+        .iter()
+        .map(|(name, bytes, encoding)| {
+            let path = dir.write(name, bytes).to_string_lossy().into_owned();
+            FileWithLines::read(path, encoding, &LoadProgress::default(), None).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn export_transcodes_other_encodings() {
+    let dir = TempDir::new("export_encodings");
+    let latin1 = Encoding::for_label(b"latin1").unwrap();
+    let latin1_log: &[u8] =
+        b"2023-05-03 10:25:50 caf\xe9\n\tcontinued \xb5s\n2023-05-03 10:25:52 na\xefve\n";
+    // read as UTF-8, a byte order mark and an invalid byte
+    let invalid_log: &[u8] = b"\xef\xbb\xbf2023-05-03 10:25:51 caf\xe9\n";
+    let files = read_files(
+        &dir,
+        &[
+            ("latin1.log", latin1_log, latin1),
+            ("invalid.log", invalid_log, UTF_8),
+        ],
+    );
+    let parser = TimestampParser::default();
+    let lines = merge_files(&files, &parser).unwrap();
+
+    let mut out: Vec<u8> = Vec::new();
+    write_lines(&mut out, &files, &lines, false).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "2023-05-03 10:25:50 caf\u{e9}\n\tcontinued \u{b5}s\n\
+         2023-05-03 10:25:51 caf\u{fffd}\n\
+         2023-05-03 10:25:52 na\u{ef}ve\n"
+    );
+
+    let mut out: Vec<u8> = Vec::new();
+    write_lines(&mut out, &files, &lines, true).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "latin1.log:2023-05-03 10:25:50 caf\u{e9}\nlatin1.log:\tcontinued \u{b5}s\n\
+         invalid.log:2023-05-03 10:25:51 caf\u{fffd}\n\
+         latin1.log:2023-05-03 10:25:52 na\u{ef}ve\n"
+    );
+}
+
+#[test]
+fn export_utf16_as_utf8() {
+    let dir = TempDir::new("export_utf16");
+    let utf16 =
+        |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    let log = utf16("\u{feff}2023-05-03 10:25:50 one\n\tcontinued\n");
+    let files = read_files(
+        &dir,
+        &[
+            ("utf16.log", &log, UTF_8),
+            ("utf8.log", b"2023-05-03 10:25:51 two\n", UTF_8),
+        ],
+    );
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let mut out: Vec<u8> = Vec::new();
+    write_lines(&mut out, &files, &lines, false).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "2023-05-03 10:25:50 one\n\tcontinued\n2023-05-03 10:25:51 two\n"
+    );
+
+    let mut out: Vec<u8> = Vec::new();
+    write_lines(&mut out, &files, &lines, true).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "utf16.log:2023-05-03 10:25:50 one\nutf16.log:\tcontinued\nutf8.log:2023-05-03 10:25:51 two\n"
+    );
+}
+
+#[test]
+fn export_prefixes_tell_same_names_apart() {
+    let files: Vec<FileWithLines> = ["/bundle/host1/app.log", "/bundle/host2/app.log"]
+        .iter()
+        .enumerate()
+        .map(|(i, filename)| {
+            FileWithLines::new(
+                filename.to_string(),
+                format!("2023-05-03 10:25:5{} - host{}\n", i, i + 1),
+            )
+        })
+        .collect();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let mut out: Vec<u8> = Vec::new();
+    write_lines(&mut out, &files, &lines, true).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "host1/app.log:2023-05-03 10:25:50 - host1\nhost2/app.log:2023-05-03 10:25:51 - host2\n"
+    );
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod test;
+
+use crate::mergeline::Line;
+use crate::text::FileWithLines;
+use log::Level;
+use regex::Regex;
+use std::collections::HashSet;
+
+/// Decides which records of the merged view are shown, all conditions have to hold.
+#[derive(Clone, Default)]
+pub struct Filter {
+    pub include: Option<Regex>,
+    pub exclude: Option<Regex>,
+    /// Records less severe than this, or without any level, are hidden.
+    pub min_level: Option<Level>,
+    /// Indices of source files whose records are hidden.
+    pub hidden_files: HashSet<usize>,
+    /// Time window in microseconds, both ends inclusive.
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl Filter {
+    pub fn is_active(&self) -> bool {
+        self.include.is_some()
+            || self.exclude.is_some()
+            || self.min_level.is_some()
+            || !self.hidden_files.is_empty()
+            || self.from.is_some()
+            || self.to.is_some()
+    }
+
+    pub fn matches(&self, line: &Line, record: &str) -> bool {
+        if self.hidden_files.contains(&line.source_file) {
+            return false;
+        }
+        if self.from.is_some_and(|from| line.timestamp < from)
+            || self.to.is_some_and(|to| line.timestamp > to)
+        {
+            return false;
+        }
+        if let Some(include) = &self.include {
+            if !include.is_match(record) {
+                return false;
+            }
+        }
+        if let Some(exclude) = &self.exclude {
+            if exclude.is_match(record) {
+                return false;
+            }
+        }
+        if let Some(min_level) = self.min_level {
+            match get_level(record) {
+                Some(level) => level <= min_level,
+                None => false,
+            }
+        } else {
+            true
+        }
+    }
+
+    /// Returns indices into `lines` of all records passing the filter.
+    pub fn apply(&self, files: &[FileWithLines], lines: &[Line]) -> Vec<usize> {
+        if !self.is_active() {
+            return (0..lines.len()).collect();
+        }
+
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| match files[line.source_file].get_record(line) {
+                Ok(record) => self.matches(line, &record),
+                Err(_) => false,
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// Finds the first level keyword in the first line of `record`, e.g. `INFO` or `ERROR`.
+pub fn get_level(record: &str) -> Option<Level> {
+    let first_line = record.split('\n').next().unwrap_or_default();
+    first_line
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .find_map(|word| match word {
+            "TRACE" => Some(Level::Trace),
+            "DEBUG" => Some(Level::Debug),
+            "INFO" => Some(Level::Info),
+            "WARN" | "WARNING" => Some(Level::Warn),
+            "ERROR" | "FATAL" | "CRITICAL" => Some(Level::Error),
+            _ => None,
+        })
+}
+
+/// Cycles through no level restriction and then from the most to the least severe level.
+pub fn next_min_level(level: Option<Level>) -> Option<Level> {
+    match level {
+        None => Some(Level::Error),
+        Some(Level::Error) => Some(Level::Warn),
+        Some(Level::Warn) => Some(Level::Info),
+        Some(Level::Info) => Some(Level::Debug),
+        Some(Level::Debug) => Some(Level::Trace),
+        Some(Level::Trace) => None,
+    }
+}
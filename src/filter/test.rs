@@ -0,0 +1,77 @@
+use super::*;
+use crate::text::fixtures::generate_files;
+use crate::text::merge_files;
+use crate::timestamp::TimestampParser;
+
+#[test]
+fn empty_filter_shows_everything() {
+    let files = generate_files();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+    assert_eq!(Filter::default().apply(&files, &lines), vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn include_and_exclude() {
+    let files = generate_files();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let filter = Filter {
+        include: Some(Regex::new("session=1").unwrap()),
+        ..Default::default()
+    };
+    assert_eq!(filter.apply(&files, &lines), vec![0, 1]);
+
+    let filter = Filter {
+        exclude: Some(Regex::new("Main.java").unwrap()),
+        ..Default::default()
+    };
+    assert_eq!(filter.apply(&files, &lines), vec![0, 1, 3]);
+}
+
+#[test]
+fn min_level() {
+    let files = generate_files();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let filter = Filter {
+        min_level: Some(Level::Warn),
+        ..Default::default()
+    };
+    assert_eq!(filter.apply(&files, &lines), vec![1, 2]);
+}
+
+#[test]
+fn hidden_files() {
+    let files = generate_files();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let filter = Filter {
+        hidden_files: HashSet::from([1]),
+        ..Default::default()
+    };
+    assert_eq!(filter.apply(&files, &lines), vec![0, 2]);
+}
+
+#[test]
+fn level_detection() {
+    assert_eq!(
+        get_level("2023-05-03 10:25:50.262116 src\\main.rs INFO  - main - start"),
+        Some(Level::Info)
+    );
+    assert_eq!(get_level("[WARNING] disk almost full"), Some(Level::Warn));
+    assert_eq!(get_level("nothing to see\nERROR in the next line"), None);
+    assert_eq!(get_level("INFORMATION is not a level"), None);
+}
+
+#[test]
+fn time_window() {
+    let files = generate_files();
+    let lines = merge_files(&files, &TimestampParser::default()).unwrap();
+
+    let filter = Filter {
+        from: Some(lines[1].timestamp),
+        to: Some(lines[2].timestamp),
+        ..Default::default()
+    };
+    assert_eq!(filter.apply(&files, &lines), vec![1, 2]);
+}
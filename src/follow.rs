@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod test;
+
+use crate::decompress;
+use crate::text::FileWithLines;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use log::*;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Watches logfiles for data written to them after loading, like `tail -F`.
+pub struct Follower {
+    watched: Vec<Watched>,
+}
+
+/// A followed logfile and how far it has been read.
+struct Watched {
+    source_file: usize,
+    path: String,
+    offset: u64,
+    /// Identifies the file behind `path`, a new one means the logfile was rotated by renaming it.
+    id: Option<FileId>,
+    /// Start of a line still being written.
+    pending: Vec<u8>,
+    /// Bytes at the start of `pending` which are in the loaded text already, its last line lacking a line break.
+    loaded: usize,
+    encoding: &'static Encoding,
+    /// Set once the file was truncated in place, the text read before is gone from it.
+    truncated: bool,
+}
+
+type FileId = (u64, u64);
+
+impl Follower {
+    /// Follows the plain files among `files`, compressed files and archives are not written to.
+    /// UTF-16 files are left out too, their line breaks are not single bytes.
+    pub fn new(files: &[FileWithLines]) -> Follower {
+        let mut watched: Vec<Watched> = Vec::new();
+        for (source_file, file) in files.iter().enumerate() {
+            let path = file.filename();
+            if file.encoding() == UTF_16LE || file.encoding() == UTF_16BE {
+                info!("Follower::new - not following UTF-16 file={}", path);
+                continue;
+            }
+            // text appended later continues the last line, text after a rotation does not
+            let pending = file
+                .unfinished_line()
+                .map(|line| line.into_owned())
+                .unwrap_or_default();
+            match decompress::is_plain(&path) {
+                Ok(true) => watched.push(Watched {
+                    source_file,
+                    id: fs::metadata(&path).ok().and_then(|m| file_id(&m)),
+                    offset: file.raw_size() as u64,
+                    path,
+                    loaded: pending.len(),
+                    pending,
+                    encoding: file.encoding(),
+                    truncated: false,
+                }),
+                Ok(false) => {}
+                Err(err) => warn!("Follower::new - cannot follow file={}, error={}", path, err),
+            }
+        }
+        info!("Follower::new - following count={}", watched.len());
+        Follower { watched }
+    }
+
+    /// Number of files followed.
+    pub fn len(&self) -> usize {
+        self.watched.len()
+    }
+
+    /// Reads what was written since the last call, returns the complete new lines by source file.
+    pub fn poll(&mut self) -> Vec<(usize, String)> {
+        let mut result: Vec<(usize, String)> = Vec::new();
+        for watched in &mut self.watched {
+            match watched.read_lines() {
+                Ok(Some(text)) => result.push((watched.source_file, text)),
+                Ok(None) => {}
+                Err(err) => warn!(
+                    "Follower::poll - cannot read file={}, error={}",
+                    watched.path, err
+                ),
+            }
+        }
+        result
+    }
+
+    /// Source files truncated in place since the last call, e.g. by copytruncate.
+    pub fn take_truncated(&mut self) -> Vec<usize> {
+        self.watched
+            .iter_mut()
+            .filter_map(|watched| {
+                std::mem::take(&mut watched.truncated).then_some(watched.source_file)
+            })
+            .collect()
+    }
+}
+
+impl Watched {
+    fn read_lines(&mut self) -> io::Result<Option<String>> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // rotated away, the new file is not there yet
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let id = file_id(&metadata);
+        // a renamed logfile keeps its text, a truncated one does not
+        let truncated = id == self.id && metadata.len() < self.offset;
+        if id != self.id || truncated {
+            info!(
+                "Watched::read_lines - file rotated, reading from the start, file={}, truncated={}",
+                self.path, truncated
+            );
+            self.id = id;
+            self.offset = 0;
+            self.truncated |= truncated;
+            // the last line of the old file will not be finished anymore
+            if !self.pending.is_empty() {
+                self.pending.push(b'\n');
+            }
+        }
+
+        if metadata.len() > self.offset {
+            let mut file = fs::File::open(&self.path)?;
+            file.seek(SeekFrom::Start(self.offset))?;
+            let read = file
+                .take(metadata.len() - self.offset)
+                .read_to_end(&mut self.pending)?;
+            self.offset += read as u64;
+        }
+
+        match self.pending.iter().rposition(|b| *b == b'\n') {
+            Some(last) => {
+                let lines: Vec<u8> = self.pending.drain(..=last).skip(self.loaded).collect();
+                self.loaded = 0;
+                let (text, _) = self.encoding.decode_without_bom_handling(&lines);
+                Ok(Some(text.into_owned()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Without inodes only truncation is detected as rotation.
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<FileId> {
+    None
+}
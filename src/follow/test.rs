@@ -0,0 +1,143 @@
+use super::*;
+use crate::text::fixtures::TempDir;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Writes `text` into a logfile of its own directory, which is removed once the test is done with it.
+fn temp_log(name: &str, text: &str) -> (TempDir, PathBuf, Follower) {
+    let dir = TempDir::new(&format!("follow_{}", name));
+    let path = dir.write(name, text);
+    let file = FileWithLines::new(path.to_string_lossy().into_owned(), text.to_string());
+    (dir, path, Follower::new(&[file]))
+}
+
+fn write(path: &PathBuf, text: &str) {
+    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(text.as_bytes()).unwrap();
+}
+
+#[test]
+fn appended_lines_test() {
+    let (_dir, path, mut follower) = temp_log("appended.log", "2023-05-03 10:00:00 first\n");
+    assert_eq!(follower.len(), 1);
+    assert!(follower.poll().is_empty());
+
+    write(&path, "2023-05-03 10:00:01 second\n2023-05-03 10:00");
+    assert_eq!(
+        follower.poll(),
+        vec![(0, String::from("2023-05-03 10:00:01 second\n"))]
+    );
+
+    // the unfinished line waits for its line break
+    assert!(follower.poll().is_empty());
+    write(&path, ":02 third\n");
+    assert_eq!(
+        follower.poll(),
+        vec![(0, String::from("2023-05-03 10:00:02 third\n"))]
+    );
+}
+
+#[test]
+fn truncated_test() {
+    let (_dir, path, mut follower) = temp_log("truncated.log", "2023-05-03 10:00:00 first\n");
+    fs::write(&path, "2023-05-03 11:00:00 new\n").unwrap();
+    assert_eq!(
+        follower.poll(),
+        vec![(0, String::from("2023-05-03 11:00:00 new\n"))]
+    );
+}
+
+/// A loaded file ending in `2023-05-03 10:00:01 unfinished` without a line break, and its follower.
+fn unfinished_log(name: &str) -> (TempDir, PathBuf, FileWithLines, Follower) {
+    let text = "2023-05-03 10:00:00 first\n2023-05-03 10:00:01 unfinished";
+    let dir = TempDir::new(&format!("follow_{}", name));
+    let path = dir.write(name, text);
+    let file = FileWithLines::new(path.to_string_lossy().into_owned(), text.to_string());
+    let follower = Follower::new(std::slice::from_ref(&file));
+    (dir, path, file, follower)
+}
+
+#[test]
+fn unfinished_line_continues_test() {
+    let (_dir, path, mut file, mut follower) = unfinished_log("continued.log");
+    write(&path, " line\n");
+    let written = follower.poll();
+    assert_eq!(written, vec![(0, String::from(" line\n"))]);
+    file.append(&written[0].1);
+    assert_eq!(
+        file.get_ith_line(1).unwrap(),
+        "2023-05-03 10:00:01 unfinished line"
+    );
+}
+
+#[test]
+fn unfinished_line_ends_on_truncation_test() {
+    let (_dir, path, mut file, mut follower) = unfinished_log("truncated.log");
+    fs::write(&path, "2023-05-03 11:00:00 new\n").unwrap();
+    let written = follower.poll();
+    assert_eq!(
+        written,
+        vec![(0, String::from("\n2023-05-03 11:00:00 new\n"))]
+    );
+    assert_eq!(file.append(&written[0].1), 2);
+    assert_eq!(
+        file.get_ith_line(1).unwrap(),
+        "2023-05-03 10:00:01 unfinished"
+    );
+    assert_eq!(file.get_ith_line(2).unwrap(), "2023-05-03 11:00:00 new");
+}
+
+#[cfg(unix)]
+#[test]
+fn unfinished_line_ends_on_rename_test() {
+    let (_dir, path, _file, mut follower) = unfinished_log("renamed.log");
+    fs::rename(&path, path.with_extension("log.1")).unwrap();
+    fs::write(&path, "2023-05-03 11:00:00 a new and longer first line\n").unwrap();
+    assert_eq!(
+        follower.poll(),
+        vec![(
+            0,
+            String::from("\n2023-05-03 11:00:00 a new and longer first line\n")
+        )]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn renamed_test() {
+    let (_dir, path, mut follower) = temp_log("renamed.log", "2023-05-03 10:00:00 first\n");
+    fs::rename(&path, path.with_extension("log.1")).unwrap();
+    assert!(follower.poll().is_empty());
+
+    fs::write(&path, "2023-05-03 10:00:00 first line of a longer file\n").unwrap();
+    assert_eq!(
+        follower.poll(),
+        vec![(
+            0,
+            String::from("2023-05-03 10:00:00 first line of a longer file\n")
+        )]
+    );
+}
+
+#[test]
+fn latin1_test() {
+    let dir = TempDir::new("follow_latin1");
+    let path = dir.write("latin1.log", b"2023-05-03 10:00:00 caf\xe9\n");
+    let latin1 = Encoding::for_label(b"latin1").unwrap();
+    let file = FileWithLines::read(
+        path.to_string_lossy().into_owned(),
+        latin1,
+        &crate::text::LoadProgress::default(),
+        None,
+    )
+    .unwrap();
+    let mut follower = Follower::new(&[file]);
+
+    // the offset counts the bytes on disk, not those of the decoded text
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"2023-05-03 10:00:01 na\xefve\n").unwrap();
+    assert_eq!(
+        follower.poll(),
+        vec![(0, String::from("2023-05-03 10:00:01 naïve\n"))]
+    );
+}
@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod test;
+
+use memchr::memchr_iter;
+
+/// Lines from one kept line start to the next.
+pub const LINE_STEP: usize = 16;
+
+/// Where the lines of a text start, kept for every `LINE_STEP`th line only.
+/// The lines in between are found by searching the text for line breaks from the start before them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// Byte offsets of the lines `0`, `LINE_STEP`, `2 * LINE_STEP` and so on.
+    pub starts: Vec<usize>,
+    /// Line breaks in the text.
+    pub breaks: usize,
+    /// Start of the line after the last line break.
+    pub tail: usize,
+    /// Bytes of text indexed.
+    pub size: usize,
+}
+
+impl Default for LineIndex {
+    fn default() -> LineIndex {
+        LineIndex {
+            starts: vec![0],
+            breaks: 0,
+            tail: 0,
+            size: 0,
+        }
+    }
+}
+
+impl LineIndex {
+    pub fn of(text: &[u8]) -> LineIndex {
+        let mut index = LineIndex::default();
+        index.push(text);
+        index
+    }
+
+    /// Indexes `chunk`, which continues the text indexed so far.
+    pub fn push(&mut self, chunk: &[u8]) {
+        for i in memchr_iter(b'\n', chunk) {
+            self.breaks += 1;
+            self.tail = self.size + i + 1;
+            if self.breaks.is_multiple_of(LINE_STEP) {
+                self.starts.push(self.tail);
+            }
+        }
+        self.size += chunk.len();
+    }
+
+    /// The same index without a last line lacking a line break, which may have grown since.
+    pub fn without_last_line(&self) -> LineIndex {
+        LineIndex {
+            size: self.tail,
+            ..self.clone()
+        }
+    }
+
+    /// Lines of the text, a last line without a line break counts as one, the empty text has none.
+    pub fn len(&self) -> usize {
+        self.breaks + usize::from(self.tail < self.size)
+    }
+
+    /// The nearest line start kept at or before `line`, and the lines from there to `line`.
+    /// Lines after the last one start at the end of the text.
+    pub fn nearest_start(&self, line: usize) -> (usize, usize) {
+        match line > self.breaks {
+            true => (self.size, 0),
+            false => (self.starts[line / LINE_STEP], line % LINE_STEP),
+        }
+    }
+
+    /// Whether `line` ends with a line break, all but a last unfinished line do.
+    pub fn has_break(&self, line: usize) -> bool {
+        line < self.breaks
+    }
+
+    /// Whether the kept starts fit the number of line breaks, e.g. when read from the cache.
+    pub fn is_consistent(&self) -> bool {
+        self.starts.len() == self.breaks / LINE_STEP + 1
+            && self.starts.first() == Some(&0)
+            && self.tail <= self.size
+    }
+}
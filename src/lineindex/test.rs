@@ -0,0 +1,42 @@
+use super::*;
+
+#[test]
+fn lines_of_text_test() {
+    assert_eq!(LineIndex::of(b"").len(), 0);
+    assert_eq!(LineIndex::default().len(), 0);
+    assert_eq!(LineIndex::of(b"a\n").len(), 1);
+    assert_eq!(LineIndex::of(b"a\nb").len(), 2);
+    assert_eq!(LineIndex::of(b"a\nb\n").len(), 2);
+    assert!(LineIndex::of(b"a\nb").has_break(0));
+    assert!(!LineIndex::of(b"a\nb").has_break(1));
+}
+
+#[test]
+fn keeps_every_step_test() {
+    let text = "line\n".repeat(2 * LINE_STEP + 3);
+    let index = LineIndex::of(text.as_bytes());
+    assert_eq!(index.starts, vec![0, 5 * LINE_STEP, 10 * LINE_STEP]);
+    assert_eq!(index.len(), 2 * LINE_STEP + 3);
+    assert!(index.is_consistent());
+
+    assert_eq!(index.nearest_start(LINE_STEP - 1), (0, LINE_STEP - 1));
+    assert_eq!(index.nearest_start(2 * LINE_STEP + 1), (10 * LINE_STEP, 1));
+    assert_eq!(index.nearest_start(2 * LINE_STEP + 4), (text.len(), 0));
+}
+
+#[test]
+fn pushed_in_chunks_test() {
+    let text = "2023-05-03 10:25:50 - one\n\tcontinued\n".repeat(LINE_STEP) + "unfinished";
+    let mut index = LineIndex::default();
+    for chunk in text.as_bytes().chunks(7) {
+        index.push(chunk);
+    }
+    assert_eq!(index, LineIndex::of(text.as_bytes()));
+    assert_eq!(index.len(), 2 * LINE_STEP + 1);
+
+    // the unfinished line is indexed again once it grew
+    let mut resumed = index.without_last_line();
+    assert_eq!(resumed.len(), 2 * LINE_STEP);
+    resumed.push(b"unfinished\nnext");
+    assert_eq!(resumed, LineIndex::of((text.clone() + "\nnext").as_bytes()));
+}
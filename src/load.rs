@@ -0,0 +1,409 @@
+#[cfg(test)]
+mod test;
+
+use crate::cache::{FileIndex, IndexCache};
+use crate::decompress::{self, Member};
+use crate::mergeline::{merge_all, Line};
+use crate::text::{modified_time, unique_names, FileWithLines, LoadProgress};
+use crate::timestamp::{LineError, TimestampParser};
+use log::*;
+use std::fmt;
+use std::io;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+/// Why a file could not be loaded.
+#[derive(Debug)]
+pub enum LoadErrorKind {
+    /// Opening or reading it failed.
+    Io(io::Error),
+    /// It, or the archive holding it, is not valid compressed data.
+    Decode(io::Error),
+    /// Its lines could not be parsed, e.g. none of them has a timestamp.
+    Parse(LineError),
+    /// Loading was cancelled before it was done.
+    Cancelled,
+    /// The task loading it ended without a result.
+    Failed(String),
+}
+
+/// A file which could not be loaded, and why.
+#[derive(Debug)]
+pub struct LoadError {
+    pub file: String,
+    pub kind: LoadErrorKind,
+}
+
+impl LoadError {
+    fn new(file: &str, kind: LoadErrorKind) -> LoadError {
+        LoadError {
+            file: file.to_string(),
+            kind,
+        }
+    }
+
+    /// Tells a file which cannot be read from one which cannot be decompressed.
+    fn read(file: &str, err: io::Error) -> LoadError {
+        let kind = match err.kind() {
+            io::ErrorKind::InvalidData
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::UnexpectedEof => LoadErrorKind::Decode(err),
+            _ => LoadErrorKind::Io(err),
+        };
+        LoadError::new(file, kind)
+    }
+}
+
+impl fmt::Display for LoadErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadErrorKind::Io(err) => write!(f, "cannot read, {}", err),
+            LoadErrorKind::Decode(err) => write!(f, "cannot decompress, {}", err),
+            LoadErrorKind::Parse(err) => write!(f, "{}", err),
+            LoadErrorKind::Cancelled => write!(f, "cancelled"),
+            LoadErrorKind::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "file={}, error={}", self.file, self.kind)
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            LoadErrorKind::Io(err) | LoadErrorKind::Decode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A file read and parsed on its own.
+type LoadedFile = (FileWithLines, Vec<Line>);
+
+/// Files read, parsed and merged by a `Loader`, and those which failed.
+pub struct Loaded {
+    pub files: Vec<FileWithLines>,
+    pub lines: Vec<Line>,
+    /// In the order the files were given.
+    pub failed: Vec<LoadError>,
+}
+
+/// Reads, parses and merges files on background tasks, the UI polls it for progress and the result.
+pub struct Loader {
+    filenames: Vec<String>,
+    /// Shown in the status, told apart like in the log view.
+    names: Vec<String>,
+    progress: Vec<Arc<LoadProgress>>,
+    result: oneshot::Receiver<Loaded>,
+}
+
+impl Loader {
+    /// Starts loading `filenames`, has to be called within the tokio runtime.
+    /// An archive among them is loaded by its members, listed in the background.
+    /// Indices of the files in `cache` are reused and updated.
+    pub fn start(
+        filenames: Vec<String>,
+        parser: Arc<TimestampParser>,
+        cache: Option<Arc<IndexCache>>,
+    ) -> Loader {
+        let progress: Vec<Arc<LoadProgress>> = filenames
+            .iter()
+            .map(|_| Arc::new(LoadProgress::default()))
+            .collect();
+        let (sender, result) = oneshot::channel();
+        tokio::spawn(load(
+            filenames.clone(),
+            progress.clone(),
+            parser,
+            cache,
+            sender,
+        ));
+
+        Loader {
+            names: unique_names(&filenames),
+            filenames,
+            progress,
+            result,
+        }
+    }
+
+    /// Stops the tasks still reading or parsing, the files not done yet fail as cancelled.
+    pub fn cancel(&self) {
+        info!(
+            "Loader::cancel - cancelling, count={}",
+            self.filenames.len()
+        );
+        for progress in &self.progress {
+            progress.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the loaded files once all are merged, with the files which could not be loaded.
+    pub fn try_result(&mut self) -> Option<Loaded> {
+        match self.result.try_recv() {
+            Ok(loaded) => Some(loaded),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Closed) => Some(stopped(&self.filenames)),
+        }
+    }
+
+    /// Waits until all files are merged, for callers without a UI to keep responsive.
+    pub async fn result(self) -> Loaded {
+        match self.result.await {
+            Ok(loaded) => loaded,
+            Err(_) => stopped(&self.filenames),
+        }
+    }
+
+    /// Fraction of the work done, reading and parsing a file count half each.
+    pub fn ratio(&self) -> f64 {
+        let done: f64 = self
+            .progress
+            .iter()
+            .map(|p| {
+                let read = fraction(
+                    p.bytes_read.load(Ordering::Relaxed),
+                    p.size.load(Ordering::Relaxed),
+                );
+                let parsed = fraction(
+                    p.lines_parsed.load(Ordering::Relaxed) as u64,
+                    p.lines.load(Ordering::Relaxed) as u64,
+                );
+                (read + parsed) / 2.0
+            })
+            .sum();
+        done / self.progress.len().max(1) as f64
+    }
+
+    /// One line per file with the bytes read and lines parsed so far.
+    pub fn status(&self) -> String {
+        self.names
+            .iter()
+            .zip(&self.progress)
+            .map(|(name, p)| {
+                format!(
+                    "{}  {}/{} KiB read, {}/{} lines parsed",
+                    name,
+                    p.bytes_read.load(Ordering::Relaxed) / 1024,
+                    p.size.load(Ordering::Relaxed) / 1024,
+                    p.lines_parsed.load(Ordering::Relaxed),
+                    p.lines.load(Ordering::Relaxed)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Lists the members of an archive on a background task, the UI polls it for the result.
+pub struct Listing {
+    pub archive: String,
+    result: oneshot::Receiver<io::Result<Vec<Member>>>,
+}
+
+impl Listing {
+    /// Starts listing `archive`, has to be called within the tokio runtime.
+    pub fn start(archive: String) -> Listing {
+        let (sender, result) = oneshot::channel();
+        let path = archive.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = sender.send(decompress::list_members(&path));
+        });
+        Listing { archive, result }
+    }
+
+    /// Returns the members once they are listed, or why they could not be.
+    pub fn try_result(&mut self) -> Option<io::Result<Vec<Member>>> {
+        match self.result.try_recv() {
+            Ok(members) => Some(members),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Closed) => {
+                Some(Err(io::Error::other("listing stopped unexpectedly")))
+            }
+        }
+    }
+}
+
+/// Fails all `filenames`, the task loading them ended without sending a result.
+fn stopped(filenames: &[String]) -> Loaded {
+    Loaded {
+        files: Vec::new(),
+        lines: Vec::new(),
+        failed: filenames
+            .iter()
+            .map(|filename| {
+                LoadError::new(
+                    filename,
+                    LoadErrorKind::Failed(String::from("loading stopped unexpectedly")),
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Compressed files read fewer bytes than their size on disk, so done files always count as done.
+fn fraction(done: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => (done as f64 / total as f64).min(1.0),
+    }
+}
+
+async fn load(
+    filenames: Vec<String>,
+    progress: Vec<Arc<LoadProgress>>,
+    parser: Arc<TimestampParser>,
+    cache: Option<Arc<IndexCache>>,
+    sender: oneshot::Sender<Loaded>,
+) {
+    let tasks: Vec<_> = filenames
+        .into_iter()
+        .zip(progress)
+        .enumerate()
+        .map(|(i, (filename, progress))| {
+            let parser = parser.clone();
+            let cache = cache.clone();
+            let task = filename.clone();
+            let handle = tokio::task::spawn_blocking(move || {
+                load_entry(i, task, &progress, &parser, cache.as_deref())
+            });
+            (filename, handle)
+        })
+        .collect();
+
+    // a file failing leaves the others to be shown without it
+    let mut loaded: Vec<LoadedFile> = Vec::new();
+    let mut failed: Vec<LoadError> = Vec::new();
+    for (filename, handle) in tasks {
+        let results = handle.await.unwrap_or_else(|err| {
+            vec![Err(LoadError::new(
+                &filename,
+                LoadErrorKind::Failed(format!("loading failed, {}", err)),
+            ))]
+        });
+        for result in results {
+            match result {
+                Ok(file) => loaded.push(file),
+                Err(err) => {
+                    warn!("load - cannot load {}", err);
+                    failed.push(err);
+                }
+            }
+        }
+    }
+
+    // the position among the loaded files is the source index of their lines
+    let (files, mut file_lines): (Vec<FileWithLines>, Vec<Vec<Line>>) = loaded.into_iter().unzip();
+    for (source_file, lines) in file_lines.iter_mut().enumerate() {
+        for line in lines.iter_mut() {
+            line.source_file = source_file;
+        }
+    }
+    let merged = tokio::task::spawn_blocking(move || merge_all(&file_lines)).await;
+    let loaded = match merged {
+        Ok(lines) => {
+            info!(
+                "load - files merged, total count={}, failed={}",
+                lines.len(),
+                failed.len()
+            );
+            Loaded {
+                files,
+                lines,
+                failed,
+            }
+        }
+        Err(err) => {
+            failed.extend(files.iter().map(|file| {
+                LoadError::new(
+                    &file.filename(),
+                    LoadErrorKind::Failed(format!("merging failed, {}", err)),
+                )
+            }));
+            Loaded {
+                files: Vec::new(),
+                lines: Vec::new(),
+                failed,
+            }
+        }
+    };
+    let _ = sender.send(loaded);
+}
+
+/// Loads `filename`, or every member of it if it is an archive, in the order of their paths.
+fn load_entry(
+    source_file: usize,
+    filename: String,
+    progress: &LoadProgress,
+    parser: &TimestampParser,
+    cache: Option<&IndexCache>,
+) -> Vec<Result<LoadedFile, LoadError>> {
+    let members = match decompress::split_member(&filename) {
+        (_, Some(_)) => Vec::new(),
+        (archive, None) => match decompress::list_members(archive) {
+            Ok(members) => members,
+            Err(err) => return vec![Err(LoadError::read(&filename, err))],
+        },
+    };
+    if members.is_empty() {
+        return vec![load_file(source_file, filename, progress, parser, cache)];
+    }
+
+    info!(
+        "load_entry - loading archive={}, members={}",
+        filename,
+        members.len()
+    );
+    let mut paths: Vec<String> = members
+        .iter()
+        .map(|member| decompress::member_path(&filename, &member.name))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| load_file(source_file, path, progress, parser, cache))
+        .collect()
+}
+
+fn load_file(
+    source_file: usize,
+    filename: String,
+    progress: &LoadProgress,
+    parser: &TimestampParser,
+    cache: Option<&IndexCache>,
+) -> Result<LoadedFile, LoadError> {
+    let parser_key = parser.fingerprint(&filename, modified_time(&filename));
+    let index = cache.and_then(|cache| cache.find(&filename, parser_key));
+    // reading and parsing both stop with an error once cancelled
+    let cancelled = |err| match progress.cancelled.load(Ordering::Relaxed) {
+        true => LoadError::new(&filename, LoadErrorKind::Cancelled),
+        false => err,
+    };
+
+    let encoding = parser.encoding_for(&filename);
+    let mut file = FileWithLines::read(filename.clone(), encoding, progress, index.as_ref())
+        .map_err(|err| cancelled(LoadError::read(&filename, err)))?;
+    let (lines, out_of_order) = file
+        .get_annotated_lines_with_progress(source_file, parser, progress, index.as_ref())
+        .map_err(|err| cancelled(LoadError::new(&filename, LoadErrorKind::Parse(err))))?;
+    file.set_out_of_order(out_of_order);
+
+    // an index still covering the whole file stays as it is
+    let indexed = index.map(|index| index.line_index);
+    if let Some(cache) = cache.filter(|_| indexed.as_ref() != Some(file.line_index())) {
+        let stored =
+            FileIndex::new(&file, &lines, parser_key).and_then(|index| cache.store(&index));
+        if let Err(err) = stored {
+            warn!(
+                "load_file - cannot store index, file={}, error={}",
+                filename, err
+            );
+        }
+    }
+    Ok((file, lines))
+}
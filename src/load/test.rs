@@ -0,0 +1,115 @@
+use super::*;
+use crate::text::fixtures::TempDir;
+use std::fs;
+use std::time::Duration;
+
+/// Writes `files` into a directory removed once the test is done with it, returns their paths.
+fn temp_logs(name: &str, files: &[(&str, &str)]) -> (TempDir, Vec<String>) {
+    let dir = TempDir::new(&format!("load_{}", name));
+    let paths = files
+        .iter()
+        .map(|(filename, text)| dir.write(filename, text).to_string_lossy().into_owned())
+        .collect();
+    (dir, paths)
+}
+
+async fn wait(loader: &mut Loader) -> Loaded {
+    loop {
+        if let Some(result) = loader.try_result() {
+            return result;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn files_are_merged_in_order() {
+    let (_dir, filenames) = temp_logs(
+        "merged",
+        &[
+            (
+                "one.log",
+                "2023-05-03 10:25:50 - one\n2023-05-03 10:25:52 - three\n",
+            ),
+            ("two.log", "2023-05-03 10:25:51 - two\n"),
+        ],
+    );
+    let mut loader = Loader::start(
+        filenames.clone(),
+        Arc::new(TimestampParser::default()),
+        None,
+    );
+    let loaded = wait(&mut loader).await;
+
+    assert!(loaded.failed.is_empty());
+    assert_eq!(loaded.files[0].filename(), filenames[0]);
+    let order: Vec<(usize, usize)> = loaded
+        .lines
+        .iter()
+        .map(|l| (l.source_file, l.index))
+        .collect();
+    assert_eq!(order, vec![(0, 0), (1, 0), (0, 1)]);
+    assert_eq!(loader.ratio(), 1.0);
+}
+
+#[tokio::test]
+async fn cancelled_load_fails_every_file() {
+    let text = "2023-05-03 10:25:50 - line\n".repeat(100_000);
+    let (_dir, filenames) = temp_logs("cancelled", &[("big.log", &text)]);
+    let mut loader = Loader::start(filenames, Arc::new(TimestampParser::default()), None);
+    loader.cancel();
+    let loaded = wait(&mut loader).await;
+    assert!(loaded.files.is_empty());
+    assert!(matches!(loaded.failed[0].kind, LoadErrorKind::Cancelled));
+}
+
+#[tokio::test]
+async fn missing_file_fails_with_its_name() {
+    let mut loader = Loader::start(
+        vec![String::from("does/not/exist.log")],
+        Arc::new(TimestampParser::default()),
+        None,
+    );
+    let loaded = wait(&mut loader).await;
+    assert_eq!(loaded.failed[0].file, "does/not/exist.log");
+    assert!(matches!(
+        &loaded.failed[0].kind,
+        LoadErrorKind::Io(err) if err.kind() == io::ErrorKind::NotFound
+    ));
+}
+
+#[tokio::test]
+async fn good_files_load_without_the_failed_ones() {
+    let (_dir, filenames) = temp_logs(
+        "partial",
+        &[
+            ("a.log", "no timestamp here\n"),
+            ("b.log", "2023-05-03 10:25:51 - two\n"),
+            ("c.log.gz", ""),
+            ("d.log", "2023-05-03 10:25:50 - one\n"),
+        ],
+    );
+    fs::write(&filenames[2], b"\x1f\x8b\x08\x00 truncated").unwrap();
+    let mut loader = Loader::start(
+        filenames.clone(),
+        Arc::new(TimestampParser::default()),
+        None,
+    );
+    let loaded = wait(&mut loader).await;
+
+    // the lines refer to the files which were loaded
+    let names: Vec<String> = loaded.files.iter().map(|f| f.filename()).collect();
+    assert_eq!(names, vec![filenames[1].clone(), filenames[3].clone()]);
+    let order: Vec<usize> = loaded.lines.iter().map(|l| l.source_file).collect();
+    assert_eq!(order, vec![1, 0]);
+
+    assert_eq!(loaded.failed.len(), 2);
+    assert_eq!(loaded.failed[0].file, filenames[0]);
+    assert!(matches!(loaded.failed[0].kind, LoadErrorKind::Parse(_)));
+    assert_eq!(
+        loaded.failed[0].to_string(),
+        format!("file={}, error=found no timestamps", filenames[0])
+    );
+    assert_eq!(loaded.failed[1].file, filenames[2]);
+    assert!(matches!(loaded.failed[1].kind, LoadErrorKind::Decode(_)));
+}
@@ -1,35 +1,220 @@
+use clap::{Parser, Subcommand};
 use log::*;
-use std::{env, io};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
 
 mod app;
 use app::run_app;
+mod cache;
+mod cli;
+mod config;
+mod decompress;
+mod export;
+mod filter;
+mod follow;
+mod lineindex;
+mod load;
 mod mergeline;
+mod scan;
 mod text;
 mod timestamp;
 
-#[tokio::main()]
-async fn main() -> Result<(), io::Error> {
-    log4rs::init_file("config/log4rs.yaml", Default::default()).unwrap();
-    info!("main - start");
+use cache::IndexCache;
+use config::{Config, ScanConfig, DEFAULT_CONFIG};
+use filter::Filter;
+use scan::ScanOptions;
+use timestamp::{parse_user_time, TimestampParser};
+
+/// Read from the working directory, logchuck runs without logging when it is missing.
+const LOG_CONFIG: &str = "config/log4rs.yaml";
+
+/// Scan a directory of logfiles and merge them by timestamp.
+#[derive(Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+struct Args {
+    /// Folder to read logfiles from, defaults to the current working directory
+    path: Option<String>,
+
+    /// Configuration file with user-defined timestamp formats
+    #[arg(long, global = true, default_value = DEFAULT_CONFIG)]
+    config: PathBuf,
+
+    /// Index every file again instead of reusing the line breaks and timestamps found before
+    #[arg(long)]
+    no_cache: bool,
+
+    #[command(flatten)]
+    time: TimeArgs,
+
+    #[command(flatten)]
+    scan: ScanArgs,
 
-    let args: Vec<String> = env::args().collect();
-
-    let path: String = match args.len() {
-        1 => std::env::current_dir()
-            .unwrap()
-            .as_os_str()
-            .to_str()
-            .unwrap()
-            .to_string(),
-        2 => args[1].clone(),
-        _ => {
-            eprintln!("wrong number of arguments, options:\n    1 arguments = read from current working directory\n    1 argument = path to folder to read from");
-            return Ok(());
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Restricts the merged lines to a time window, times are in UTC and need a date, e.g. `2023-05-03 14:32`.
+#[derive(clap::Args)]
+struct TimeArgs {
+    /// Hide lines before this time
+    #[arg(long, value_parser = parse_cli_time)]
+    from: Option<i64>,
+    /// Hide lines after this time
+    #[arg(long, value_parser = parse_cli_time)]
+    to: Option<i64>,
+}
+
+impl TimeArgs {
+    fn to_filter(&self) -> Filter {
+        Filter {
+            from: self.from,
+            to: self.to,
+            ..Default::default()
         }
-    };
+    }
+}
+
+/// Which files of a folder are listed, added to the `scan` section of the configuration.
+#[derive(clap::Args)]
+struct ScanArgs {
+    /// Levels of subfolders to read logfiles from, 0 reads only the folder itself
+    #[arg(long, global = true)]
+    depth: Option<usize>,
+    /// Only read files matching this glob, e.g. '*.log' or 'host*/app/*.log', may be repeated
+    #[arg(long, global = true)]
+    include: Vec<String>,
+    /// Leave out files and folders matching this glob, may be repeated
+    #[arg(long, global = true)]
+    exclude: Vec<String>,
+}
 
-    run_app(&path)?;
+impl ScanArgs {
+    fn to_options(&self, config: &ScanConfig) -> io::Result<ScanOptions> {
+        let include: Vec<String> = config
+            .include
+            .iter()
+            .chain(&self.include)
+            .cloned()
+            .collect();
+        let exclude: Vec<String> = config
+            .exclude
+            .iter()
+            .chain(&self.exclude)
+            .cloned()
+            .collect();
+        ScanOptions::new(
+            self.depth.or(config.depth).unwrap_or_default(),
+            &include,
+            &exclude,
+        )
+    }
+}
+
+fn parse_cli_time(input: &str) -> Result<i64, String> {
+    parse_user_time(input, None).map_err(|err| err.error_message)
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Merge logfiles by timestamp
+    Merge {
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Write into this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Prefix each line with the name of its source file
+        #[arg(short, long)]
+        prefix_filename: bool,
+        #[command(flatten)]
+        time: TimeArgs,
+    },
+    /// Print records matching a regex from all logfiles in a folder, in merged order
+    Grep {
+        pattern: String,
+        path: PathBuf,
+        #[command(flatten)]
+        time: TimeArgs,
+    },
+    /// Print line counts and time spans of all logfiles in a folder
+    Stats { path: PathBuf },
+}
+
+/// Scripts run logchuck from anywhere, a missing or broken logging setup must not stop it.
+fn init_logging() {
+    if !Path::new(LOG_CONFIG).exists() {
+        return;
+    }
+    if let Err(err) = log4rs::init_file(LOG_CONFIG, Default::default()) {
+        eprintln!(
+            "cannot set up logging, config={}, error={}",
+            LOG_CONFIG, err
+        );
+    }
+}
 
+#[tokio::main()]
+async fn main() {
+    init_logging();
+    info!("main - start");
+
+    // scripts get the message, not the debug output of the error
+    if let Err(err) = run(Args::parse()).await {
+        error!("main - failed, error={}", err);
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
     info!("main - end");
+}
+
+async fn run(args: Args) -> Result<(), io::Error> {
+    // only the default location may be missing
+    let config = Config::load(&args.config, args.config != Path::new(DEFAULT_CONFIG))?;
+    let parser = TimestampParser::with_custom(&config.timestamp_formats)
+        .and_then(|parser| parser.with_corrections(&config.time_corrections))
+        .and_then(|parser| parser.with_encodings(&config.encodings))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.error_message))?;
+    info!(
+        "main - config loaded, timestamp_formats={}",
+        config.timestamp_formats.len()
+    );
+
+    let scan = args.scan.to_options(&config.scan)?;
+
+    match args.command {
+        Some(Command::Merge {
+            files,
+            output,
+            prefix_filename,
+            time,
+        }) => cli::merge(files, output, prefix_filename, &time.to_filter(), parser).await?,
+        Some(Command::Grep {
+            pattern,
+            path,
+            time,
+        }) => cli::grep(&pattern, &path, &time.to_filter(), parser, &scan).await?,
+        Some(Command::Stats { path }) => cli::stats(&path, parser, &scan).await?,
+        None => {
+            let path: String = match args.path {
+                Some(path) => path,
+                None => std::env::current_dir()
+                    .unwrap()
+                    .as_os_str()
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+            };
+            let cache = match args.no_cache {
+                true => None,
+                false => config
+                    .cache_dir
+                    .or_else(IndexCache::default_dir)
+                    .map(IndexCache::new),
+            };
+            run_app(&path, args.time.to_filter(), parser, cache, scan)?;
+        }
+    }
     Ok(())
 }
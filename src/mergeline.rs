@@ -1,16 +1,22 @@
 use core::fmt;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 
+#[cfg(test)]
+pub mod fixtures;
 #[cfg(test)]
 mod test;
 
+/// A single log record: the timestamped line at `index` followed by `line_count - 1` continuation lines.
 #[derive(Debug, Clone)]
 pub struct Line {
     pub timestamp: i64,
     pub source_file: usize,
     pub index: usize,
+    pub line_count: usize,
 }
 
-pub fn merge(left: &Vec<Line>, right: &Vec<Line>) -> Vec<Line> {
+pub fn merge(left: &[Line], right: &[Line]) -> Vec<Line> {
     let mut index_left: usize = 0; // maximum = left.len()
     let mut index_right: usize = 0; // maximum = right.len()
 
@@ -56,19 +62,40 @@ pub fn merge(left: &Vec<Line>, right: &Vec<Line>) -> Vec<Line> {
     result
 }
 
+/// Merges all `sequences` in one pass, keeping the order within each of them.
+/// Only the next record of every sequence is in the heap, so the work is O(n·log k) for k sequences.
+pub fn merge_all(sequences: &[Vec<Line>]) -> Vec<Line> {
+    let mut result: Vec<Line> = Vec::with_capacity(sequences.iter().map(|s| s.len()).sum());
+    // (next record, sequence, position of the record in the sequence)
+    let mut heads: BinaryHeap<Reverse<(&Line, usize, usize)>> = sequences
+        .iter()
+        .enumerate()
+        .filter_map(|(i, sequence)| sequence.first().map(|line| Reverse((line, i, 0))))
+        .collect();
+
+    while let Some(Reverse((line, sequence, position))) = heads.pop() {
+        result.push(line.clone());
+        if let Some(next) = sequences[sequence].get(position + 1) {
+            heads.push(Reverse((next, sequence, position + 1)));
+        }
+    }
+
+    result
+}
+
 impl fmt::Display for Line {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Line({}, {}, {})",
-            self.timestamp, self.source_file, self.index
+            "Line({}, {}, {}, {})",
+            self.timestamp, self.source_file, self.index, self.line_count
         )
     }
 }
 
 impl std::cmp::PartialEq for Line {
     fn eq(&self, other: &Self) -> bool {
-        self.timestamp == other.timestamp
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
@@ -80,8 +107,13 @@ impl std::cmp::PartialOrd for Line {
     }
 }
 
+/// Records with the same timestamp keep the order of their source files, then their order within the file.
 impl std::cmp::Ord for Line {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        std::cmp::Ord::cmp(&self.timestamp, &other.timestamp)
+        (self.timestamp, self.source_file, self.index).cmp(&(
+            other.timestamp,
+            other.source_file,
+            other.index,
+        ))
     }
 }
@@ -0,0 +1,30 @@
+//! Records shared by the merge tests and `benches/merge.rs`, which compiles this file on its own.
+
+use super::Line;
+
+pub fn generate_line(timestamp: i64, source_file: usize, index: usize) -> Line {
+    Line {
+        timestamp,
+        source_file,
+        index,
+        line_count: 1,
+    }
+}
+
+/// `count` files of `len` records each, like rotated logs covering the same hours.
+/// Timestamps grow by less than `2^step_bits` per record, few bits give many equal ones.
+pub fn generate_files(count: usize, len: usize, step_bits: u32) -> Vec<Vec<Line>> {
+    let mut seed: u64 = 42;
+    (0..count)
+        .map(|source_file| {
+            let mut timestamp: i64 = 0;
+            (0..len)
+                .map(|index| {
+                    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    timestamp += (seed >> (64 - step_bits)) as i64;
+                    generate_line(timestamp, source_file, index)
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -1,17 +1,12 @@
+use super::fixtures::{generate_files, generate_line};
 use super::*;
 
-fn generate_line(timestamp: i64, source_file: usize, index: usize) -> Line {
-    Line {
-        timestamp,
-        source_file,
-        index,
-    }
-}
-
 #[test]
 fn cmp_implementation_on_line() {
     assert!(generate_line(7, 1, 0) < generate_line(30, 1, 0));
-    assert!(generate_line(30, 1337, 10) == generate_line(30, 1, 0));
+    assert!(generate_line(30, 1337, 10) > generate_line(30, 1, 0));
+    assert!(generate_line(30, 1, 10) > generate_line(30, 1, 0));
+    assert!(generate_line(30, 1, 10) == generate_line(30, 1, 10));
     assert!(generate_line(50, 1, 0) > generate_line(30, 1, 0));
 }
 
@@ -43,3 +38,56 @@ fn test_merge() {
     assert_eq!(result[6].timestamp, 30);
     assert_eq!(result[7].timestamp, 45);
 }
+
+#[test]
+fn equal_timestamps_merge_by_source_then_index() {
+    let file_one: Vec<Line> = vec![generate_line(10, 0, 0), generate_line(10, 0, 1)];
+    let file_two: Vec<Line> = vec![generate_line(10, 1, 0), generate_line(10, 1, 1)];
+
+    // the order of the arguments does not matter
+    for result in [merge(&file_one, &file_two), merge(&file_two, &file_one)] {
+        let order: Vec<(usize, usize)> = result.iter().map(|l| (l.source_file, l.index)).collect();
+        assert_eq!(order, vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+    }
+}
+
+#[test]
+fn merge_keeps_order_within_file() {
+    // a clock stepping back must not reorder the lines of its file
+    let file_one: Vec<Line> = vec![generate_line(20, 0, 0), generate_line(10, 0, 1)];
+    let file_two: Vec<Line> = vec![generate_line(15, 1, 0)];
+
+    let result = merge(&file_two, &file_one);
+    let order: Vec<(usize, usize)> = result.iter().map(|l| (l.source_file, l.index)).collect();
+    assert_eq!(order, vec![(1, 0), (0, 0), (0, 1)]);
+}
+
+#[test]
+fn merge_all_same_as_pairwise() {
+    // equal timestamps across files end up in the same order as merging the files one by one
+    let files = generate_files(7, 100, 4);
+    let pairwise = files
+        .iter()
+        .fold(Vec::new(), |all_lines, file| merge(&all_lines, file));
+    let merged = merge_all(&files);
+
+    assert_eq!(merged.len(), 700);
+    assert!(merged.windows(2).all(|w| w[0] <= w[1]));
+    let key = |l: &Line| (l.timestamp, l.source_file, l.index);
+    assert_eq!(
+        merged.iter().map(key).collect::<Vec<_>>(),
+        pairwise.iter().map(key).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn merge_all_edge_cases() {
+    assert!(merge_all(&[]).is_empty());
+    assert!(merge_all(&[Vec::new(), Vec::new()]).is_empty());
+
+    let file_one: Vec<Line> = vec![generate_line(20, 0, 0), generate_line(10, 0, 1)];
+    let file_two: Vec<Line> = vec![generate_line(15, 1, 0)];
+    let result = merge_all(&[file_one, Vec::new(), file_two]);
+    let order: Vec<(usize, usize)> = result.iter().map(|l| (l.source_file, l.index)).collect();
+    assert_eq!(order, vec![(1, 0), (0, 0), (0, 1)]);
+}
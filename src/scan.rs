@@ -0,0 +1,489 @@
+#[cfg(test)]
+mod test;
+
+use crate::decompress::{self, Member};
+use crate::export::EXPORT_FILENAME;
+use crate::text::looks_binary;
+use glob::{MatchOptions, Pattern};
+use log::*;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Bytes at the start of a file checked for binary content.
+const BINARY_PROBE: usize = 8192;
+
+/// Extensions of compressed rotated files, dropped before looking for the rotation suffix.
+const COMPRESSED_EXTENSIONS: [&str; 7] = [".gz", ".tgz", ".zst", ".xz", ".bz2", ".zip", ".tar"];
+
+/// `*` in a glob with a `/` does not match across directories, `**` does.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Clone)]
+pub struct FileEntry {
+    /// Canonical path, loaded files, the selection and the cache go by it.
+    pub filename: String,
+    pub file_size: u64,
+    /// Starts with bytes which are not text, shown with a warning.
+    pub binary: bool,
+    /// Directory holding the file relative to the scanned one, e.g. `host1/app`, empty for the scanned one.
+    pub dir: String,
+    /// A tar or zip archive, loaded by its members.
+    pub archive: bool,
+    /// Members of the archive once listed, see `list_members`.
+    pub members: Option<Vec<FileEntry>>,
+}
+
+impl FileEntry {
+    fn dir_components(&self) -> Vec<&str> {
+        self.dir.split('/').filter(|c| !c.is_empty()).collect()
+    }
+
+    /// Lists the members of an archive unless done before, decompressing it in full unless it is a zip file.
+    pub fn list_members(&mut self) -> io::Result<&[FileEntry]> {
+        if self.members.is_none() {
+            let members = match self.archive {
+                true => decompress::list_members(&self.filename)?,
+                false => Vec::new(),
+            };
+            self.set_members(&members);
+        }
+        Ok(self.members.as_deref().unwrap_or_default())
+    }
+
+    /// Keeps `members` of the archive, listed by `list_members` or on a background task.
+    pub fn set_members(&mut self, members: &[Member]) {
+        info!(
+            "FileEntry::set_members - file={}, count={}",
+            self.filename,
+            members.len()
+        );
+        self.members = Some(
+            members
+                .iter()
+                .map(|m| FileEntry {
+                    filename: decompress::member_path(&self.filename, &m.name),
+                    file_size: m.size,
+                    binary: false,
+                    dir: self.dir.clone(),
+                    archive: false,
+                    members: None,
+                })
+                .collect(),
+        );
+    }
+}
+
+/// Files are ordered like in a tree, those of a directory before its subdirectories.
+impl Ord for FileEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.dir_components(), &self.filename).cmp(&(other.dir_components(), &other.filename))
+    }
+}
+
+impl PartialOrd for FileEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for FileEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.filename == other.filename
+    }
+}
+
+impl Eq for FileEntry {}
+
+/// Which files `scan_directory` lists.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Levels of subdirectories to descend into, 0 lists only the scanned directory.
+    pub depth: usize,
+    /// Only files matching one of these are listed, all files if there are none.
+    pub include: Vec<Pattern>,
+    /// Files and directories matching one of these are left out.
+    pub exclude: Vec<Pattern>,
+}
+
+impl ScanOptions {
+    /// Globs with a `/` match the path relative to the scanned directory, others the name only.
+    pub fn new(depth: usize, include: &[String], exclude: &[String]) -> io::Result<ScanOptions> {
+        let compile = |globs: &[String]| {
+            globs
+                .iter()
+                .map(|glob| {
+                    Pattern::new(glob).map_err(|err| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("invalid glob={}, error={}", glob, err),
+                        )
+                    })
+                })
+                .collect::<io::Result<Vec<Pattern>>>()
+        };
+        Ok(ScanOptions {
+            depth,
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    fn is_excluded(&self, relative: &str) -> bool {
+        matches_any(&self.exclude, relative)
+    }
+
+    fn is_included(&self, relative: &str) -> bool {
+        self.include.is_empty() || matches_any(&self.include, relative)
+    }
+}
+
+fn matches_any(globs: &[Pattern], relative: &str) -> bool {
+    let name = relative.rsplit('/').next().unwrap_or(relative);
+    globs.iter().any(|glob| match glob.as_str().contains('/') {
+        true => glob.matches_with(relative, MATCH_OPTIONS),
+        false => glob.matches_with(name, MATCH_OPTIONS),
+    })
+}
+
+pub fn scan_directory(path: &Path, options: &ScanOptions) -> Result<Vec<FileEntry>, io::Error> {
+    let mut result: Vec<FileEntry> = Vec::new();
+    scan_into(&mut result, path, "", options.depth, options)?;
+    result.sort();
+    info!(
+        "scan_directory - path={}, depth={}, count={}",
+        path.display(),
+        options.depth,
+        result.len()
+    );
+    Ok(result)
+}
+
+/// Lists the files of `path`, which is `dir` relative to the scanned directory, and of its subdirectories up to `depth` levels down.
+fn scan_into(
+    result: &mut Vec<FileEntry>,
+    path: &Path,
+    dir: &str,
+    depth: usize,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    for item in std::fs::read_dir(path)? {
+        let item_path = item?.path();
+        let Some(file) = item_path.file_name() else {
+            continue;
+        };
+        let file = match file.to_os_string().into_string() {
+            Ok(file) => file,
+            Err(err_file) => {
+                return Err(io::Error::other(format!(
+                    "filename is not Unicode, filename={:?}",
+                    err_file
+                )))
+            }
+        };
+        if file.as_bytes()[0] == b'.' {
+            continue;
+        }
+        let relative = match dir.is_empty() {
+            true => file.clone(),
+            false => format!("{}/{}", dir, file),
+        };
+        if options.is_excluded(&relative) {
+            continue;
+        }
+
+        if item_path.is_dir() {
+            if depth > 0 {
+                // an unreadable subdirectory leaves the others listed
+                if let Err(err) = scan_into(result, &item_path, &relative, depth - 1, options) {
+                    warn!(
+                        "scan_into - cannot scan directory={}, error={}",
+                        item_path.display(),
+                        err
+                    );
+                }
+            }
+            continue;
+        }
+        if file == EXPORT_FILENAME || !options.is_included(&relative) {
+            continue;
+        }
+
+        // a dangling symlink or a file removed meanwhile leaves the others listed
+        let found = std::fs::metadata(&item_path)
+            .and_then(|metadata| Ok((metadata, item_path.canonicalize()?)));
+        let (metadata, fullpath) = match found {
+            Ok(found) => found,
+            Err(err) => {
+                warn!(
+                    "scan_into - cannot read file={}, error={}",
+                    item_path.display(),
+                    err
+                );
+                continue;
+            }
+        };
+        if !metadata.is_file() || metadata.len() == 0 {
+            continue;
+        }
+        let filename = fullpath.to_string_lossy().into_owned();
+        result.push(probe(filename, metadata.len(), dir));
+    }
+    Ok(())
+}
+
+/// Reads only the start of `filename` to tell archives and binary files apart, members are listed on demand.
+fn probe(filename: String, file_size: u64, dir: &str) -> FileEntry {
+    let (binary, archive) = match decompress::head(&filename, BINARY_PROBE) {
+        Ok(Some(head)) => (looks_binary(&head), false),
+        Ok(None) => (false, true),
+        Err(err) => {
+            warn!("probe - cannot read file={}, error={}", filename, err);
+            (false, false)
+        }
+    };
+    FileEntry {
+        filename,
+        file_size,
+        binary,
+        dir: dir.to_string(),
+        archive,
+        members: None,
+    }
+}
+
+/// Filenames of `entries` to load, the members of archives instead of the archives.
+/// An archive which cannot be listed is kept, loading it reports the error.
+pub fn loadable_files(entries: Vec<FileEntry>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for mut entry in entries {
+        if !entry.archive {
+            result.push(entry.filename);
+            continue;
+        }
+        match entry.list_members() {
+            Ok(members) => result.extend(members.iter().map(|m| m.filename.clone())),
+            Err(err) => {
+                warn!(
+                    "loadable_files - cannot list archive members, file={}, error={}",
+                    entry.filename, err
+                );
+                result.push(entry.filename);
+            }
+        }
+    }
+    result
+}
+
+/// A row of the file list shown as a tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileRow {
+    /// A directory below the scanned one, e.g. `host1/app`, with the number of files in it and its subdirectories.
+    Dir {
+        path: String,
+        depth: usize,
+        files: usize,
+        collapsed: bool,
+    },
+    /// Index of a file in the scanned entries.
+    File { entry: usize, depth: usize },
+    /// An archive in the scanned entries, its members are listed below it only when `expanded`.
+    Archive {
+        entry: usize,
+        depth: usize,
+        expanded: bool,
+    },
+    /// Stands for the members of the expanded archive `entry` while they are listed.
+    Listing { entry: usize, depth: usize },
+    /// Index of a member in the members of the archive `entry`.
+    Member {
+        entry: usize,
+        member: usize,
+        depth: usize,
+    },
+    /// A logfile and its rotated files, e.g. `host1/app.log` for `app.log`, `app.log.1` and `app.log.2.gz`.
+    /// `entries` has the current file first, its members are listed below it only when `expanded`.
+    Family {
+        path: String,
+        depth: usize,
+        entries: Vec<usize>,
+        expanded: bool,
+    },
+}
+
+/// Name of the logfile `name` was rotated from, e.g. `app.log` for `app.log.1`, `app.log.2.gz`,
+/// `app.20230501.log.tar.gz` or `app-2023-05-01.log`, `name` itself for anything else.
+/// Small numbers only count after a dot, `device_1.log` is a file of its own.
+pub fn rotation_base(name: &str) -> String {
+    static SUFFIXES: OnceLock<[Regex; 3]> = OnceLock::new();
+    let suffixes = SUFFIXES.get_or_init(|| {
+        [
+            // app.log.1
+            Regex::new(r"^(.+)\.\d{1,3}$").unwrap(),
+            // app.log-20230501, app.20230501.log, app-2023-05-01_10-00.log
+            Regex::new(
+                r"^(.+?)[._-](?:\d{8}|\d{4}-\d{2}-\d{2})(?:[T_-]?\d{2}-?\d{2}(?:-?\d{2})?)?(\.[A-Za-z][A-Za-z0-9]*)?$",
+            )
+            .unwrap(),
+            // app.1.log
+            Regex::new(r"^(.+)\.\d{1,3}(\.[A-Za-z][A-Za-z0-9]*)$").unwrap(),
+        ]
+    });
+
+    let mut stem = name;
+    while let Some(extension) = COMPRESSED_EXTENSIONS.iter().find(|e| stem.ends_with(*e)) {
+        stem = &stem[..stem.len() - extension.len()];
+    }
+    suffixes
+        .iter()
+        .find_map(|suffix| suffix.captures(stem))
+        .map(|captures| {
+            let extension = captures.get(2).map(|e| e.as_str()).unwrap_or_default();
+            format!("{}{}", &captures[1], extension)
+        })
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Files of `entries` rotated from the same logfile in the same directory, by the path of the family.
+/// A logfile without rotated files is not grouped.
+fn families(entries: &[FileEntry]) -> HashMap<String, Vec<usize>> {
+    let mut families: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let base = rotation_base(&file_name(&entry.filename));
+        let path = match entry.dir.is_empty() {
+            true => base,
+            false => format!("{}/{}", entry.dir, base),
+        };
+        families.entry(path).or_default().push(i);
+    }
+    families.retain(|_, members| members.len() > 1);
+    for (path, members) in families.iter_mut() {
+        let base = path.rsplit('/').next().unwrap_or(path);
+        // the current logfile first, the rotated ones as sorted by name
+        members.sort_by_key(|i| file_name(&entries[*i].filename) != base);
+    }
+    families
+}
+
+fn file_name(filename: &str) -> String {
+    Path::new(filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Rows of `entries`, as sorted by `scan_directory`, grouped under their directories and rotation families.
+/// The contents of the directories in `collapsed` are left out. The members of families, by path, and of
+/// archives, by filename, only show when in `expanded`.
+pub fn file_rows(
+    entries: &[FileEntry],
+    collapsed: &HashSet<String>,
+    expanded: &HashSet<String>,
+) -> Vec<FileRow> {
+    let families = families(entries);
+    let mut family_of: HashMap<usize, &str> = HashMap::new();
+    for (path, members) in &families {
+        for i in members {
+            family_of.insert(*i, path);
+        }
+    }
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for entry in entries {
+        let components = entry.dir_components();
+        for end in 1..=components.len() {
+            *counts.entry(components[..end].join("/")).or_default() += 1;
+        }
+    }
+
+    let mut rows: Vec<FileRow> = Vec::new();
+    let mut open: Vec<&str> = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let components = entry.dir_components();
+        let common = open
+            .iter()
+            .zip(&components)
+            .take_while(|(a, b)| a == b)
+            .count();
+        open.truncate(common);
+
+        // a directory shows once its parent is expanded
+        let hidden = |end: usize| (1..end).any(|e| collapsed.contains(&components[..e].join("/")));
+        for end in common + 1..=components.len() {
+            open.push(components[end - 1]);
+            if !hidden(end) {
+                let path = components[..end].join("/");
+                rows.push(FileRow::Dir {
+                    files: counts[&path],
+                    collapsed: collapsed.contains(&path),
+                    path,
+                    depth: end - 1,
+                });
+            }
+        }
+        if hidden(components.len() + 1) {
+            continue;
+        }
+        let depth = components.len();
+        match family_of.get(&i) {
+            None => push_file(&mut rows, entries, expanded, i, depth),
+            // a family shows where its first file by name is
+            Some(path) if families[*path].iter().min() == Some(&i) => {
+                let members = &families[*path];
+                let is_expanded = expanded.contains(*path);
+                rows.push(FileRow::Family {
+                    path: path.to_string(),
+                    depth,
+                    entries: members.clone(),
+                    expanded: is_expanded,
+                });
+                if is_expanded {
+                    for entry in members {
+                        push_file(&mut rows, entries, expanded, *entry, depth + 1);
+                    }
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    rows
+}
+
+/// Adds the row of the file `entry`, and of its members if it is an expanded archive.
+fn push_file(
+    rows: &mut Vec<FileRow>,
+    entries: &[FileEntry],
+    expanded: &HashSet<String>,
+    entry: usize,
+    depth: usize,
+) {
+    let file = &entries[entry];
+    if !file.archive {
+        rows.push(FileRow::File { entry, depth });
+        return;
+    }
+    let is_expanded = expanded.contains(&file.filename);
+    rows.push(FileRow::Archive {
+        entry,
+        depth,
+        expanded: is_expanded,
+    });
+    match (is_expanded, &file.members) {
+        (true, Some(members)) => rows.extend((0..members.len()).map(|member| FileRow::Member {
+            entry,
+            member,
+            depth: depth + 1,
+        })),
+        (true, None) => rows.push(FileRow::Listing {
+            entry,
+            depth: depth + 1,
+        }),
+        (false, _) => {}
+    }
+}
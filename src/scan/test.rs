@@ -0,0 +1,294 @@
+use super::*;
+use crate::text::fixtures::TempDir;
+
+/// A support bundle with logs nested in host folders.
+fn bundle(name: &str) -> TempDir {
+    let dir = TempDir::new(&format!("scan_{}", name));
+    for (path, text) in [
+        ("top.log", "top\n"),
+        ("host1/app/server.log", "server\n"),
+        ("host1/app/server.tmp", "tmp\n"),
+        ("host1/system.log", "system\n"),
+        ("host2/app/server.log", "server\n"),
+        ("host2/app/deep/trace.log", "trace\n"),
+        ("archive/old.log", "old\n"),
+    ] {
+        dir.write(path, text);
+    }
+    dir
+}
+
+fn scanned(dir: &Path, options: &ScanOptions) -> Vec<String> {
+    scan_directory(dir, options)
+        .unwrap()
+        .iter()
+        .map(|f| match f.dir.is_empty() {
+            true => short_name(&f.filename),
+            false => format!("{}/{}", f.dir, short_name(&f.filename)),
+        })
+        .collect()
+}
+
+fn short_name(filename: &str) -> String {
+    Path::new(filename)
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn strings(globs: &[&str]) -> Vec<String> {
+    globs.iter().map(|g| g.to_string()).collect()
+}
+
+#[test]
+fn depth_limits_recursion() {
+    let dir = bundle("depth");
+    assert_eq!(
+        scanned(dir.path(), &ScanOptions::default()),
+        vec!["top.log"]
+    );
+    assert_eq!(
+        scanned(dir.path(), &ScanOptions::new(2, &[], &[]).unwrap()),
+        vec![
+            "top.log",
+            "archive/old.log",
+            "host1/system.log",
+            "host1/app/server.log",
+            "host1/app/server.tmp",
+            "host2/app/server.log",
+        ]
+    );
+}
+
+#[test]
+fn exports_are_left_out() {
+    let dir = bundle("export");
+    dir.write(EXPORT_FILENAME, "top\n");
+    assert_eq!(
+        scanned(dir.path(), &ScanOptions::default()),
+        vec!["top.log"]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn dangling_symlinks_are_left_out() {
+    let dir = bundle("dangling");
+    std::os::unix::fs::symlink(dir.path().join("gone.log"), dir.path().join("link.log")).unwrap();
+    assert_eq!(
+        scanned(dir.path(), &ScanOptions::default()),
+        vec!["top.log"]
+    );
+}
+
+#[test]
+fn globs_include_and_exclude() {
+    let dir = bundle("globs");
+    let options =
+        ScanOptions::new(5, &strings(&["*.log"]), &strings(&["archive", "deep"])).unwrap();
+    assert_eq!(
+        scanned(dir.path(), &options),
+        vec![
+            "top.log",
+            "host1/system.log",
+            "host1/app/server.log",
+            "host2/app/server.log",
+        ]
+    );
+
+    // a glob with a slash matches the whole relative path
+    let options = ScanOptions::new(5, &strings(&["host*/app/*.log"]), &[]).unwrap();
+    assert_eq!(
+        scanned(dir.path(), &options),
+        vec!["host1/app/server.log", "host2/app/server.log"]
+    );
+
+    assert!(ScanOptions::new(0, &strings(&["[unclosed"]), &[]).is_err());
+}
+
+#[test]
+fn rows_group_files_by_directory() {
+    let dir = bundle("rows");
+    let options = ScanOptions::new(3, &strings(&["*.log"]), &strings(&["archive"])).unwrap();
+    let entries = scan_directory(dir.path(), &options).unwrap();
+    let dir_row = |path: &str, depth, files, collapsed| FileRow::Dir {
+        path: path.to_string(),
+        depth,
+        files,
+        collapsed,
+    };
+
+    assert_eq!(
+        file_rows(&entries, &HashSet::new(), &HashSet::new()),
+        vec![
+            FileRow::File { entry: 0, depth: 0 },
+            dir_row("host1", 0, 2, false),
+            FileRow::File { entry: 1, depth: 1 },
+            dir_row("host1/app", 1, 1, false),
+            FileRow::File { entry: 2, depth: 2 },
+            dir_row("host2", 0, 2, false),
+            dir_row("host2/app", 1, 2, false),
+            FileRow::File { entry: 3, depth: 2 },
+            dir_row("host2/app/deep", 2, 1, false),
+            FileRow::File { entry: 4, depth: 3 },
+        ]
+    );
+
+    let collapsed = HashSet::from([String::from("host1"), String::from("host2/app")]);
+    assert_eq!(
+        file_rows(&entries, &collapsed, &HashSet::new()),
+        vec![
+            FileRow::File { entry: 0, depth: 0 },
+            dir_row("host1", 0, 2, true),
+            dir_row("host2", 0, 2, false),
+            dir_row("host2/app", 1, 2, true),
+        ]
+    );
+}
+
+#[test]
+fn rotation_base_test() {
+    for name in [
+        "app.log",
+        "app.log.1",
+        "app.log.2.gz",
+        "app.log-20230501",
+        "app.log-20230501.gz",
+        "app.20230501.log.tar.gz",
+        "app-2023-05-01.log",
+        "app_2023-05-01_10-00.log.zst",
+        "app.3.log",
+    ] {
+        assert_eq!(rotation_base(name), "app.log", "{}", name);
+    }
+    assert_eq!(
+        rotation_base("NetworkSocket_u3000.20230201.log.tar.gz"),
+        "NetworkSocket_u3000.log"
+    );
+    // numbers which do not look like a rotation
+    assert_eq!(rotation_base("device_1.log"), "device_1.log");
+    assert_eq!(
+        rotation_base("NetworkSocket_u80.log"),
+        "NetworkSocket_u80.log"
+    );
+    assert_eq!(rotation_base("core.bin"), "core.bin");
+}
+
+#[test]
+fn rows_group_rotated_files() {
+    let dir = TempDir::new("scan_rotated");
+    for path in [
+        "app.log",
+        "app.log.1",
+        "app.log.2.gz",
+        "device_1.log",
+        "device_2.log",
+        "host1/app.log",
+    ] {
+        dir.write(path, "line\n");
+    }
+    let entries = scan_directory(dir.path(), &ScanOptions::new(1, &[], &[]).unwrap()).unwrap();
+    let family = |expanded| FileRow::Family {
+        path: String::from("app.log"),
+        depth: 0,
+        entries: vec![0, 1, 2],
+        expanded,
+    };
+
+    // a logfile without rotated files stays a file
+    assert_eq!(
+        file_rows(&entries, &HashSet::new(), &HashSet::new()),
+        vec![
+            family(false),
+            FileRow::File { entry: 3, depth: 0 },
+            FileRow::File { entry: 4, depth: 0 },
+            FileRow::Dir {
+                path: String::from("host1"),
+                depth: 0,
+                files: 1,
+                collapsed: false,
+            },
+            FileRow::File { entry: 5, depth: 1 },
+        ]
+    );
+
+    let expanded = HashSet::from([String::from("app.log")]);
+    assert_eq!(
+        file_rows(&entries, &HashSet::new(), &expanded)[..4],
+        [
+            family(true),
+            FileRow::File { entry: 0, depth: 1 },
+            FileRow::File { entry: 1, depth: 1 },
+            FileRow::File { entry: 2, depth: 1 },
+        ]
+    );
+}
+
+#[test]
+fn archive_members_are_listed_on_demand() {
+    let dir = TempDir::new("scan_archive");
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, text) in [("app/one.log", "one\n"), ("app/two.log", "two\n")] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(text.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, text.as_bytes())
+            .unwrap();
+    }
+    dir.write("bundle.tar", builder.into_inner().unwrap());
+    dir.write("top.log", "top\n");
+
+    let mut entries = scan_directory(dir.path(), &ScanOptions::default()).unwrap();
+    assert!(entries[0].archive);
+    assert!(entries[0].members.is_none());
+    assert!(!entries[1].archive);
+    let archive_row = |expanded| FileRow::Archive {
+        entry: 0,
+        depth: 0,
+        expanded,
+    };
+    assert_eq!(
+        file_rows(&entries, &HashSet::new(), &HashSet::new()),
+        vec![archive_row(false), FileRow::File { entry: 1, depth: 0 }]
+    );
+
+    let members: Vec<String> = entries[0]
+        .list_members()
+        .unwrap()
+        .iter()
+        .map(|m| m.filename.clone())
+        .collect();
+    let bundle = entries[0].filename.clone();
+    assert_eq!(
+        members,
+        vec![
+            decompress::member_path(&bundle, "app/one.log"),
+            decompress::member_path(&bundle, "app/two.log"),
+        ]
+    );
+    let expanded = HashSet::from([bundle]);
+    assert_eq!(
+        file_rows(&entries, &HashSet::new(), &expanded),
+        vec![
+            archive_row(true),
+            FileRow::Member {
+                entry: 0,
+                member: 0,
+                depth: 1,
+            },
+            FileRow::Member {
+                entry: 0,
+                member: 1,
+                depth: 1,
+            },
+            FileRow::File { entry: 1, depth: 0 },
+        ]
+    );
+
+    let mut to_load = members;
+    to_load.push(entries[1].filename.clone());
+    assert_eq!(loadable_files(entries), to_load);
+}
@@ -1,117 +1,1025 @@
-use crate::mergeline::Line;
+use crate::cache::FileIndex;
+use crate::decompress;
+use crate::lineindex::LineIndex;
+use crate::mergeline::{merge, Line};
 use crate::timestamp::*;
+use chrono::{DateTime, Utc};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use log::*;
+use memchr::memchr_iter;
+use std::borrow::Cow;
+use std::cmp::{max, min};
+use std::collections::HashMap;
 use std::fs;
-use tokio::task::JoinSet;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
 
+#[cfg(test)]
+pub mod fixtures;
 #[cfg(test)]
 mod test;
 
+/// Lines are counted into `LoadProgress::lines_parsed` in steps of this many.
+const PROGRESS_STEP: usize = 4096;
+
+/// Plain files from this size on stay on disk, their lines are read when accessed.
+const ON_DISK_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Longest byte order mark, of UTF-8.
+const BOM_LEN: usize = 3;
+
+/// Bytes of a file on disk searched for line breaks between progress updates.
+const INDEX_CHUNK: usize = 16 * 1024 * 1024;
+
+/// Bytes of a file on disk read at once when its lines are accessed.
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Bytes read from the start and the end of a file to tell its time span without loading it.
+const SPAN_PEEK: u64 = 64 * 1024;
+
+/// How far loading a file got, shared between the task loading it and the UI.
+#[derive(Debug, Default)]
+pub struct LoadProgress {
+    /// Bytes of the file on disk, or of the archive holding it.
+    pub size: AtomicU64,
+    pub bytes_read: AtomicU64,
+    /// Known once the file is read.
+    pub lines: AtomicUsize,
+    pub lines_parsed: AtomicUsize,
+    /// Set to stop reading and parsing early.
+    pub cancelled: AtomicBool,
+}
+
+impl LoadProgress {
+    fn check_cancelled(&self) -> Result<(), LineError> {
+        match self.cancelled.load(Ordering::Relaxed) {
+            true => Err(LineError {
+                error_message: String::from("loading cancelled"),
+            }),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Counts the bytes read into `progress`, fails once loading is cancelled.
+struct ProgressReader<'a, R> {
+    input: R,
+    progress: &'a LoadProgress,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.progress.cancelled.load(Ordering::Relaxed) {
+            return Err(io::Error::other("loading cancelled"));
+        }
+        let read = self.input.read(buf)?;
+        self.progress
+            .bytes_read
+            .fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+/// Skipping ahead, e.g. to the index of a zip archive, counts nothing as read.
+impl<R: Seek> Seek for ProgressReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.input.seek(pos)
+    }
+}
+
+/// A large plain file, whose lines are read block by block when accessed instead of kept in memory.
+/// Unlike a mapping, a file truncated meanwhile, e.g. rotated with copytruncate, only fails to read.
+#[derive(Debug)]
+struct OnDisk {
+    /// Bytes of the file when it was indexed, text written later is appended to `FileWithLines::text`.
+    len: usize,
+    block: Mutex<Block>,
+}
+
+/// The last block read from a file on disk.
+#[derive(Debug)]
+struct Block {
+    file: fs::File,
+    /// Byte offset of `bytes` in the file.
+    start: usize,
+    bytes: Vec<u8>,
+}
+
+impl OnDisk {
+    fn new(file: fs::File, len: usize) -> OnDisk {
+        OnDisk {
+            len,
+            block: Mutex::new(Block {
+                file,
+                start: 0,
+                bytes: Vec::new(),
+            }),
+        }
+    }
+
+    /// Calls `f` with the bytes from `from` up to at least `to` and at most the end of the block holding them.
+    fn read<T>(&self, from: usize, to: usize, f: impl FnOnce(&[u8]) -> T) -> io::Result<T> {
+        let mut guard = self.block.lock().unwrap_or_else(PoisonError::into_inner);
+        let block = &mut *guard;
+        let to = min(to, self.len);
+        let end = block.start + block.bytes.len();
+        // at least one byte is returned, `from` at the end of the block needs the next one
+        if from < block.start || from >= end || to > end {
+            let start = from - from % BLOCK_SIZE;
+            let end = min(self.len, max(to, start + BLOCK_SIZE));
+            block.bytes.clear();
+            block.file.seek(SeekFrom::Start(start as u64))?;
+            (&mut block.file)
+                .take((end - start) as u64)
+                .read_to_end(&mut block.bytes)?;
+            if block.bytes.len() < end - start {
+                block.bytes.clear();
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "file truncated since it was loaded",
+                ));
+            }
+            block.start = start;
+        }
+        Ok(f(&block.bytes[from - block.start..]))
+    }
+}
+
 #[derive(Debug)]
 pub struct FileWithLines {
+    /// Large plain files stay on disk, only the block accessed last is in memory.
+    on_disk: Option<OnDisk>,
+    /// Contents after those on disk, i.e. all of them for small or decompressed files.
     text: String,
-    line_breaks: Vec<usize>,
+    /// Where the lines of the contents on disk followed by `text` start.
+    line_index: LineIndex,
     filename: String,
+    /// Modification time in microseconds, used for timestamps without a year.
+    modified: i64,
+    /// Name of the timestamp format found in the first lines, detected on the first merge.
+    detected_format: OnceLock<Option<String>>,
+    /// Set in the UI, overrides the correction from the configuration.
+    correction: Option<TimeCorrection>,
+    /// Records found earlier than a record before them, as counted when the file was annotated.
+    out_of_order: usize,
+    /// Of the file on disk, or of its byte order mark, the text is decoded to UTF-8.
+    encoding: &'static Encoding,
+    /// Bytes decoded into the text when it was read, the size of a plain file at that time.
+    raw_size: usize,
+    /// Whether the file starts with a byte order mark, which is not part of the text.
+    bom: bool,
+    /// The bytes of a file with invalid sequences, which are replaced in the text.
+    invalid: Option<RawText>,
 }
 
-impl<'text> FileWithLines {
-    pub fn get_ith_line(&self, i: usize) -> Result<&str, LineError> {
-        match self.line_breaks.len().cmp(&(i + 1)) {
-            std::cmp::Ordering::Less => {
-                return Err(LineError {
-                    error_message: String::from("no such line"),
-                })
+/// Bytes of a file as they were read, with their own line starts.
+#[derive(Debug)]
+struct RawText {
+    bytes: Vec<u8>,
+    line_index: LineIndex,
+}
+
+impl FileWithLines {
+    pub fn new(filename: String, text: String) -> FileWithLines {
+        let line_index = LineIndex::of(text.as_bytes());
+        FileWithLines::with_line_index(filename, None, text, line_index)
+    }
+
+    fn with_line_index(
+        filename: String,
+        on_disk: Option<OnDisk>,
+        text: String,
+        line_index: LineIndex,
+    ) -> FileWithLines {
+        FileWithLines {
+            raw_size: on_disk.as_ref().map_or(0, |d| d.len) + text.len(),
+            on_disk,
+            text,
+            line_index,
+            filename,
+            modified: Utc::now().timestamp_micros(),
+            detected_format: OnceLock::new(),
+            correction: None,
+            out_of_order: 0,
+            encoding: UTF_8,
+            bom: false,
+            invalid: None,
+        }
+    }
+
+    pub fn with_modified(mut self, modified: i64) -> FileWithLines {
+        self.modified = modified;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn get_ith_line(&self, i: usize) -> Result<Cow<'_, str>, LineError> {
+        if i >= self.len() {
+            return Err(LineError {
+                error_message: String::from("no such line"),
+            });
+        }
+        let (line, _) = self.line_at(i, self.line_start(i)?)?;
+        Ok(line)
+    }
+
+    /// Returns the whole record, i.e. the timestamped line together with its continuation lines.
+    pub fn get_record(&self, line: &Line) -> Result<Cow<'_, str>, LineError> {
+        let last = line.index + line.line_count;
+        if line.line_count == 0 || last > self.len() {
+            return Err(LineError {
+                error_message: String::from("no such line"),
+            });
+        }
+        let from = self.line_start(line.index)?;
+        let to = self.skip_lines(from, line.line_count)?;
+        self.get_text(from, self.without_break(last - 1, to))
+    }
+
+    /// The record as it is in the file, in its encoding and with the byte order mark before the first line.
+    /// Invalid UTF-8 is kept as it was instead of replaced.
+    pub fn get_raw_record(&self, line: &Line) -> Result<Cow<'_, [u8]>, LineError> {
+        let last = line.index + line.line_count;
+        if line.line_count == 0 || last > self.len() {
+            return Err(LineError {
+                error_message: String::from("no such line"),
+            });
+        }
+        let record = match &self.invalid {
+            // lines appended later are only in the text
+            Some(raw) if last <= raw.line_index.len() => {
+                let (start, skip) = raw.line_index.nearest_start(line.index);
+                let from = skip_breaks(&raw.bytes, start, skip);
+                let to = skip_breaks(&raw.bytes, from, line.line_count);
+                let to = match raw.line_index.has_break(last - 1) {
+                    true => to - 1,
+                    false => to,
+                };
+                Cow::Borrowed(&raw.bytes[from..to])
             }
-            std::cmp::Ordering::Equal => return Ok(&self.text[self.line_breaks[i]..]),
-            std::cmp::Ordering::Greater => {}
+            _ => {
+                let from = self.line_start(line.index)?;
+                let to = self.skip_lines(from, line.line_count)?;
+                let to = self.without_break(last - 1, to);
+                match self.encoding == UTF_8 {
+                    true => self.get_bytes(from, to)?,
+                    false => Cow::Owned(self.encode(&self.get_text(from, to)?).into_owned()),
+                }
+            }
+        };
+        match line.index == 0 && self.bom {
+            true => Ok(Cow::Owned([&self.encode("\u{feff}"), &record[..]].concat())),
+            false => Ok(record),
         }
+    }
 
-        let res = &self.text[self.line_breaks[i]..self.line_breaks[i + 1]];
-        if res.ends_with('\n') {
-            Ok(&self.text[self.line_breaks[i]..self.line_breaks[i + 1] - 1])
+    /// `text` in the encoding of the file.
+    fn encode<'a>(&self, text: &'a str) -> Cow<'a, [u8]> {
+        // encoding_rs does not encode into UTF-16
+        if self.encoding == UTF_16LE {
+            Cow::Owned(text.encode_utf16().flat_map(u16::to_le_bytes).collect())
+        } else if self.encoding == UTF_16BE {
+            Cow::Owned(text.encode_utf16().flat_map(u16::to_be_bytes).collect())
         } else {
-            Ok(res)
+            self.encoding.encode(text).0
+        }
+    }
+
+    /// Line `i` starting at byte `start`, and the start of the line after it.
+    fn line_at(&self, i: usize, start: usize) -> Result<(Cow<'_, str>, usize), LineError> {
+        let next = self.skip_lines(start, 1)?;
+        Ok((self.get_text(start, self.without_break(i, next))?, next))
+    }
+
+    /// Byte offset of the start of line `i`, searched from the nearest start kept in the index.
+    fn line_start(&self, i: usize) -> Result<usize, LineError> {
+        let (start, skip) = self.line_index.nearest_start(i);
+        self.skip_lines(start, skip)
+    }
+
+    /// Byte offset after `count` line breaks from `from` on, or the end of the text.
+    fn skip_lines(&self, mut from: usize, mut count: usize) -> Result<usize, LineError> {
+        while count > 0 && from < self.size() {
+            let (found, next) = self.with_bytes(from, |bytes| {
+                let mut found = 0;
+                for at in memchr_iter(b'\n', bytes) {
+                    found += 1;
+                    if found == count {
+                        return (found, from + at + 1);
+                    }
+                }
+                (found, from + bytes.len())
+            })?;
+            from = next;
+            count -= found;
+        }
+        Ok(from)
+    }
+
+    /// Calls `f` with the contents from `from` to the end of the block on disk or of `text` holding them.
+    fn with_bytes<T>(&self, from: usize, f: impl FnOnce(&[u8]) -> T) -> Result<T, LineError> {
+        match &self.on_disk {
+            Some(on_disk) if from < on_disk.len => on_disk
+                .read(from, from, f)
+                .map_err(|err| self.read_error(err)),
+            _ => Ok(f(&self.text.as_bytes()[from - self.on_disk_len()..])),
+        }
+    }
+
+    /// `to`, before the line break ending line `i`.
+    fn without_break(&self, i: usize, to: usize) -> usize {
+        match self.line_index.has_break(i) {
+            true => to - 1,
+            false => to,
         }
     }
 
+    /// Text from `from` to `to`, borrowed from `text`, or read from disk with invalid UTF-8 replaced.
+    fn get_text(&self, from: usize, to: usize) -> Result<Cow<'_, str>, LineError> {
+        let on_disk_len = self.on_disk_len();
+        if from >= on_disk_len {
+            return Ok(Cow::Borrowed(
+                &self.text[from - on_disk_len..to - on_disk_len],
+            ));
+        }
+        let bytes = self.get_bytes(from, to)?.into_owned();
+        Ok(Cow::Owned(match String::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+        }))
+    }
+
+    /// Bytes from `from` to `to`, borrowed from `text` or read from disk.
+    fn get_bytes(&self, from: usize, to: usize) -> Result<Cow<'_, [u8]>, LineError> {
+        let on_disk_len = self.on_disk_len();
+        if from >= on_disk_len {
+            return Ok(Cow::Borrowed(
+                &self.text.as_bytes()[from - on_disk_len..to - on_disk_len],
+            ));
+        }
+
+        let mut bytes = self
+            .on_disk
+            .as_ref()
+            .map_or(Ok(Vec::new()), |on_disk| {
+                on_disk.read(from, to, |bytes| {
+                    bytes[..min(to, on_disk_len) - from].to_vec()
+                })
+            })
+            .map_err(|err| self.read_error(err))?;
+        if to > on_disk_len {
+            bytes.extend_from_slice(&self.text.as_bytes()[..to - on_disk_len]);
+        }
+        Ok(Cow::Owned(bytes))
+    }
+
+    fn read_error(&self, err: io::Error) -> LineError {
+        LineError {
+            error_message: format!("cannot read file={}, error={}", self.filename, err),
+        }
+    }
+
+    fn on_disk_len(&self) -> usize {
+        self.on_disk.as_ref().map_or(0, |on_disk| on_disk.len)
+    }
+
     pub fn len(&self) -> usize {
-        self.line_breaks.len() - 2
+        self.line_index.len()
     }
 
     pub fn filename(&self) -> String {
         self.filename.clone()
     }
 
-    pub async fn from_files(files: Vec<String>) -> Vec<FileWithLines> {
-        let mut futures: JoinSet<FileWithLines> = JoinSet::new();
-        for file in files {
-            futures.spawn(load_file(file.clone()));
+    /// Name of the timestamp format of this file, known after `get_annotated_lines`.
+    pub fn detected_format(&self) -> Option<&str> {
+        self.detected_format.get().and_then(|f| f.as_deref())
+    }
+
+    /// Timezone and skew applied to the timestamps of this file.
+    pub fn time_correction(&self, parser: &TimestampParser) -> TimeCorrection {
+        self.correction
+            .unwrap_or_else(|| parser.correction_for(&self.filename))
+    }
+
+    /// Number of records that had to be moved to sort the file by time.
+    pub fn out_of_order(&self) -> usize {
+        self.out_of_order
+    }
+
+    /// Keeps the number of records out of order returned with the records of this file.
+    pub fn set_out_of_order(&mut self, out_of_order: usize) {
+        self.out_of_order = out_of_order;
+    }
+
+    pub fn set_time_correction(&mut self, correction: TimeCorrection) {
+        self.correction = Some(correction);
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        &self.line_index
+    }
+
+    /// Encoding the file was decoded from, appended bytes have to be decoded alike.
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    /// Bytes of the file read, which differs from `size` for other encodings than UTF-8.
+    pub fn raw_size(&self) -> usize {
+        self.raw_size
+    }
+
+    /// Bytes of a last line without a line break as they are in the file, none if the text ends with one.
+    pub fn unfinished_line(&self) -> Result<Cow<'_, [u8]>, LineError> {
+        let len = self.len();
+        if len == 0 || self.line_index.has_break(len - 1) {
+            return Ok(Cow::Borrowed(&[]));
+        }
+        self.get_raw_record(&Line {
+            timestamp: 0,
+            source_file: 0,
+            index: len - 1,
+            line_count: 1,
+        })
+    }
+
+    /// Bytes of text read so far.
+    pub fn size(&self) -> usize {
+        self.on_disk_len() + self.text.len()
+    }
+
+    /// Forgets the contents of a file on disk which was truncated, so text appended later starts at line 0.
+    /// Returns whether there were any, the contents of other files are still at hand.
+    pub fn drop_on_disk(&mut self) -> bool {
+        if self.on_disk.take().is_none() {
+            return false;
         }
+        warn!(
+            "FileWithLines::drop_on_disk - file truncated, dropping its lines, file={}",
+            self.filename
+        );
+        self.text.clear();
+        self.line_index = LineIndex::default();
+        self.out_of_order = 0;
+        true
+    }
+
+    /// Adds text written to the file after it was read, returns the index of the first new line.
+    /// A last line without a line break continues with the start of `text`.
+    pub fn append(&mut self, text: &str) -> usize {
+        let first_new = if self.size() == 0 { 0 } else { self.len() };
+        self.text.push_str(text);
+        self.line_index.push(text.as_bytes());
+        first_new
+    }
+
+    #[cfg(test)]
+    pub fn get_annotated_lines(
+        &self,
+        source_file_index: usize,
+        parser: &TimestampParser,
+    ) -> Result<Vec<Line>, LineError> {
+        self.get_annotated_lines_with_progress(
+            source_file_index,
+            parser,
+            &LoadProgress::default(),
+            None,
+        )
+        .map(|(lines, _)| lines)
+    }
 
-        let mut result_vec: Vec<FileWithLines> = Vec::new();
+    /// Same as `get_annotated_lines`, counts the lines parsed into `progress` and stops once it is cancelled.
+    /// The records of the lines covered by `index`, from an earlier load, are taken from it.
+    /// Also returns the number of records out of order, see `set_out_of_order`.
+    pub fn get_annotated_lines_with_progress(
+        &self,
+        source_file_index: usize,
+        parser: &TimestampParser,
+        progress: &LoadProgress,
+        index: Option<&FileIndex>,
+    ) -> Result<(Vec<Line>, usize), LineError> {
+        let (mut result, from, mut latest, mut out_of_order) = match index {
+            Some(index) => self.indexed_records(source_file_index, index),
+            None => (Vec::new(), 0, i64::MIN, 0),
+        };
+        let (mut new_lines, continued) =
+            self.annotate(source_file_index, parser, from, progress)?;
+        if continued > 0 {
+            if let Some(last) = result.iter_mut().max_by_key(|l| l.index) {
+                last.line_count += continued;
+            }
+        }
 
-        while let Some(result) = futures.join_next().await {
-            let text = result.unwrap();
-            result_vec.push(text);
+        // multi-threaded loggers write slightly out of order, merging needs every file sorted
+        let new_out_of_order = count_out_of_order(&new_lines, &mut latest);
+        if new_out_of_order > 0 {
+            warn!(
+                "FileWithLines::get_annotated_lines - sorting records out of order, file={}, count={}",
+                self.filename, new_out_of_order
+            );
+            // stable, so records with equal timestamps keep their order
+            new_lines.sort_by_key(|l| l.timestamp);
         }
+        out_of_order += new_out_of_order;
 
-        result_vec
+        result = match result.is_empty() {
+            true => new_lines,
+            false => merge(&result, &new_lines),
+        };
+        if result.is_empty() {
+            return Err(LineError {
+                error_message: String::from("found no timestamps"),
+            });
+        }
+        Ok((result, out_of_order))
     }
 
-    pub fn get_annotated_lines(&self, source_file_index: usize) -> Result<Vec<Line>, LineError> {
+    /// Records from `index` but those of its last line, which may have grown since, and the index of that line.
+    /// Also returns the latest timestamp and the number of records out of order among them.
+    fn indexed_records(
+        &self,
+        source_file_index: usize,
+        index: &FileIndex,
+    ) -> (Vec<Line>, usize, i64, usize) {
+        let _ = self.detected_format.set(index.detected_format.clone());
+        let from = index.line_index.len().saturating_sub(1);
+        let records: Vec<Line> = index
+            .records
+            .iter()
+            .filter(|l| l.index < from)
+            .map(|l| Line {
+                source_file: source_file_index,
+                line_count: min(l.line_count, from - l.index),
+                ..l.clone()
+            })
+            .collect();
+        let latest = records.last().map_or(i64::MIN, |l| l.timestamp);
+        (records, from, latest, index.out_of_order)
+    }
+
+    /// Same as `get_annotated_lines` for the lines from `from` on, e.g. those added by `append`.
+    /// Also returns the number of lines before the first new record, they continue the record before `from`,
+    /// or with `from` at 0 form a record of their own once one follows.
+    /// Last, the number of new records out of order, also those earlier than `latest`, the latest timestamp before.
+    pub fn get_appended_lines(
+        &self,
+        source_file_index: usize,
+        parser: &TimestampParser,
+        from: usize,
+        mut latest: i64,
+    ) -> Result<(Vec<Line>, usize, usize), LineError> {
+        let (mut result, continued) =
+            self.annotate(source_file_index, parser, from, &LoadProgress::default())?;
+        let out_of_order = count_out_of_order(&result, &mut latest);
+        result.sort_by_key(|l| l.timestamp);
+        Ok((result, continued, out_of_order))
+    }
+
+    fn annotate(
+        &self,
+        source_file_index: usize,
+        parser: &TimestampParser,
+        from: usize,
+        progress: &LoadProgress,
+    ) -> Result<(Vec<Line>, usize), LineError> {
+        let correction = self.time_correction(parser);
+        let mut parser = parser.for_file(&self.filename);
+        let context = ParseContext {
+            modified: self.modified,
+            timezone: correction.timezone,
+        };
+        let detected = self.detected_format.get_or_init(|| {
+            let mut start = 0;
+            let lines = (0..self.len()).map_while(|i| {
+                let (line, next) = self.line_at(i, start).ok()?;
+                start = next;
+                Some(line)
+            });
+            parser.detect(lines, &context).map(String::from)
+        });
+        if let Some(name) = detected {
+            parser.prefer(name);
+        }
+
         let mut result: Vec<Line> = Vec::new();
-        for i in 0..self.len() {
-            let line = self.get_ith_line(i)?;
-            match get_timestamp_from_line(line) {
+        let mut continued = 0;
+        let mut start = self.line_start(from)?;
+        for i in from..self.len() {
+            if (i - from).is_multiple_of(PROGRESS_STEP) {
+                progress.check_cancelled()?;
+                progress.lines_parsed.store(i, Ordering::Relaxed);
+            }
+            let (line, next) = self.line_at(i, start)?;
+            start = next;
+            match parser.parse(&line, &context) {
                 Ok(timestamp) => {
+                    // lines before the first record of the file are a record of their own, stamped like it
+                    if from == 0 && result.is_empty() && continued > 0 {
+                        result.push(Line {
+                            timestamp: timestamp + correction.skew,
+                            source_file: source_file_index,
+                            index: 0,
+                            line_count: continued,
+                        });
+                        continued = 0;
+                    }
                     result.push(Line {
-                        timestamp: timestamp.timestamp_micros(),
+                        timestamp: timestamp + correction.skew,
                         source_file: source_file_index,
                         index: i,
+                        line_count: 1,
                     });
                 }
-                Err(_) => continue,
+                // lines without a timestamp (stack traces, wrapped messages) belong to the previous record
+                Err(_) => match result.last_mut() {
+                    Some(last) => last.line_count += 1,
+                    None => continued += 1,
+                },
             }
         }
+        progress.lines_parsed.store(self.len(), Ordering::Relaxed);
+        Ok((result, continued))
+    }
 
-        if result.is_empty() {
-            Err(LineError {
-                error_message: format!("found no timestamps in file={}", self.filename),
-            })
+    /// Reads the file at `path`, which may be a member of an archive, counting the bytes read into `progress`.
+    /// The text is decoded from `encoding`, unless it starts with a byte order mark.
+    /// Only the lines after those in `index`, from an earlier load, are searched for line breaks.
+    pub fn read(
+        path: String,
+        encoding: &'static Encoding,
+        progress: &LoadProgress,
+        index: Option<&FileIndex>,
+    ) -> io::Result<FileWithLines> {
+        let (archive, member) = decompress::split_member(&path);
+        let metadata = fs::metadata(archive)?;
+        progress.size.store(metadata.len(), Ordering::Relaxed);
+        let known = index.map(|index| &index.line_index);
+
+        // bytes on disk are used as they are, so only UTF-8 without a byte order mark can stay there
+        let stays_on_disk = metadata.len() >= ON_DISK_THRESHOLD
+            && encoding == UTF_8
+            && decompress::is_plain(&path)?
+            && decompress::head(&path, BOM_LEN)?
+                .is_some_and(|head| Encoding::for_bom(&head).is_none());
+        let file = if stays_on_disk {
+            FileWithLines::open(path, progress, known)?
+        } else {
+            let input = ProgressReader {
+                input: fs::File::open(archive)?,
+                progress,
+            };
+            let bytes =
+                decompress::Registry::default().decompress_seekable(Box::new(input), member)?;
+            let raw_size = bytes.len();
+            let bom = Encoding::for_bom(&bytes).is_some();
+            let (text, encoding, invalid) = decode(bytes, encoding);
+            if encoding != UTF_8 {
+                info!(
+                    "FileWithLines::read - decoded file={}, encoding={}",
+                    path,
+                    encoding.name()
+                );
+            }
+            let file = match known {
+                Some(known) => {
+                    let mut line_index = resume_index(known, text.len());
+                    line_index.push(&text.as_bytes()[line_index.size..]);
+                    FileWithLines::with_line_index(path, None, text, line_index)
+                }
+                None => FileWithLines::new(path, text),
+            };
+            FileWithLines {
+                encoding,
+                raw_size,
+                bom,
+                invalid: invalid.map(|bytes| RawText {
+                    line_index: LineIndex::of(&bytes),
+                    bytes,
+                }),
+                ..file
+            }
+        };
+        progress.lines.store(file.len(), Ordering::Relaxed);
+        match metadata.modified() {
+            Ok(modified) => {
+                Ok(file.with_modified(DateTime::<Utc>::from(modified).timestamp_micros()))
+            }
+            Err(_) => Ok(file),
+        }
+    }
+
+    /// Opens the plain file at `path` and finds its line breaks, reading it in chunks counted into `progress`.
+    /// Only the text from the last line of `known`, from an earlier load, on is searched.
+    pub fn open(
+        path: String,
+        progress: &LoadProgress,
+        known: Option<&LineIndex>,
+    ) -> io::Result<FileWithLines> {
+        let mut file = fs::File::open(&path)?;
+        let len = file.metadata()?.len() as usize;
+        info!("FileWithLines::open - indexing file={}, size={}", path, len);
+
+        let mut line_index = match known {
+            Some(known) => resume_index(known, len),
+            None => LineIndex::default(),
+        };
+        progress
+            .bytes_read
+            .fetch_add(line_index.size as u64, Ordering::Relaxed);
+        file.seek(SeekFrom::Start(line_index.size as u64))?;
+        let mut input = ProgressReader {
+            // the file may grow while it is indexed, the rest is read once followed
+            input: (&file).take((len - line_index.size) as u64),
+            progress,
+        };
+        let mut chunk = vec![0; INDEX_CHUNK];
+        loop {
+            let read = input.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            line_index.push(&chunk[..read]);
+        }
+
+        let len = line_index.size;
+        Ok(FileWithLines::with_line_index(
+            path,
+            Some(OnDisk::new(file, len)),
+            String::new(),
+            line_index,
+        ))
+    }
+}
+
+/// Counts the records earlier than any record before them, or than `latest`, which is updated.
+fn count_out_of_order(lines: &[Line], latest: &mut i64) -> usize {
+    let mut count = 0;
+    for line in lines {
+        if line.timestamp < *latest {
+            count += 1;
         } else {
-            Ok(result)
+            *latest = line.timestamp;
         }
     }
+    count
 }
 
-fn read_file_to_string(path: &String) -> String {
-    fs::read_to_string(path)
-        .unwrap_or_else(|_| panic!("Should have been able to read the file={}", path))
+/// `known`, from an earlier load of a text now `len` bytes long, without its last line which may have grown since.
+fn resume_index(known: &LineIndex, len: usize) -> LineIndex {
+    match known.size <= len {
+        true => known.without_last_line(),
+        false => {
+            warn!("resume_index - known line breaks do not fit the text");
+            LineIndex::default()
+        }
+    }
 }
 
-fn get_line_breaks(text_str: &String) -> Vec<usize> {
-    let mut line_breaks: Vec<usize> = Vec::new();
-    line_breaks.push(0);
-    let mut find_text = &text_str[0..];
-    while let Some(next) = find_text.find('\n') {
-        match line_breaks.last() {
-            None => line_breaks.push(next + 1),
-            Some(l) => line_breaks.push(l + next + 1),
-        };
-        find_text = &find_text[next + 1..];
+/// Decodes `bytes` from `encoding`, or from the encoding of the byte order mark they start with, which is returned.
+/// Invalid sequences are replaced by U+FFFD instead of failing the whole file.
+/// The bytes of such a file are returned too, unless it is UTF-16, whose line breaks are no single bytes.
+fn decode(
+    mut bytes: Vec<u8>,
+    encoding: &'static Encoding,
+) -> (String, &'static Encoding, Option<Vec<u8>>) {
+    let (encoding, bom_len) = Encoding::for_bom(&bytes).unwrap_or((encoding, 0));
+    bytes.drain(..bom_len);
+    if encoding != UTF_8 {
+        let (text, invalid) = encoding.decode_without_bom_handling(&bytes);
+        let text = text.into_owned();
+        let raw = Some(bytes).filter(|_| invalid && encoding.is_ascii_compatible());
+        return (text, encoding, raw);
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => (text, encoding, None),
+        Err(err) => {
+            let bytes = err.into_bytes();
+            (
+                String::from_utf8_lossy(&bytes).into_owned(),
+                encoding,
+                Some(bytes),
+            )
+        }
+    }
+}
+
+/// Offset after `count` line breaks in `bytes` from `from` on, or their end.
+fn skip_breaks(bytes: &[u8], from: usize, count: usize) -> usize {
+    match count {
+        0 => from,
+        _ => memchr_iter(b'\n', &bytes[from..])
+            .nth(count - 1)
+            .map_or(bytes.len(), |at| from + at + 1),
     }
-    if !text_str.ends_with('\n') {
-        line_breaks.push(text_str.len());
+}
+
+/// Whether `head`, the start of a file, looks like anything but text.
+/// Text has no NUL bytes, unless it is UTF-16, and hardly any control characters.
+pub fn looks_binary(head: &[u8]) -> bool {
+    if Encoding::for_bom(head).is_some() {
+        return false;
     }
-    line_breaks.push(text_str.len() + 1);
-    line_breaks
+    let control = head
+        .iter()
+        .filter(|b| **b < 0x20 && !matches!(**b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    head.contains(&0) || control * 10 > head.len()
 }
 
-async fn load_file(path: String) -> FileWithLines {
-    let text = read_file_to_string(&path);
-    let line_breaks: Vec<usize> = get_line_breaks(&text);
-    FileWithLines {
-        text,
-        line_breaks,
-        filename: path,
+/// Modification time in microseconds of the file at `path`, or of the archive holding it, now if it is unknown.
+pub fn modified_time(path: &str) -> i64 {
+    let (archive, _) = decompress::split_member(path);
+    fs::metadata(archive)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| DateTime::<Utc>::from(modified).timestamp_micros())
+        .unwrap_or_else(|_| Utc::now().timestamp_micros())
+}
+
+/// Time span of the plain file at `path` from the first timestamp in its head and the last one in its tail,
+/// none for compressed files and archives, which would have to be decompressed for it.
+pub fn peek_span(path: &str, parser: &TimestampParser) -> io::Result<Option<(i64, i64)>> {
+    if !decompress::is_plain(path)? {
+        return Ok(None);
+    }
+    let mut file = fs::File::open(path)?;
+    let metadata = file.metadata()?;
+    let len = metadata.len();
+    let mut head: Vec<u8> = Vec::new();
+    (&mut file).take(SPAN_PEEK).read_to_end(&mut head)?;
+    let (encoding, bom_len) = Encoding::for_bom(&head).unwrap_or((parser.encoding_for(path), 0));
+    let (head_text, _) = encoding.decode_without_bom_handling(&head[bom_len..]);
+
+    // the tail starts within a line, and for UTF-16 on the first byte of a code unit
+    let mut from = len.saturating_sub(SPAN_PEEK).max(bom_len as u64);
+    if !encoding.is_ascii_compatible() {
+        from += (from - bom_len as u64) % 2;
+    }
+    let mut tail: Vec<u8> = Vec::new();
+    file.seek(SeekFrom::Start(from))?;
+    file.read_to_end(&mut tail)?;
+    let (tail_text, _) = encoding.decode_without_bom_handling(&tail);
+    let tail_lines: Vec<&str> = tail_text
+        .lines()
+        .skip(usize::from(from > bom_len as u64))
+        .collect();
+
+    let correction = parser.correction_for(path);
+    let context = ParseContext {
+        modified: metadata
+            .modified()
+            .map(|modified| DateTime::<Utc>::from(modified).timestamp_micros())
+            .unwrap_or_else(|_| Utc::now().timestamp_micros()),
+        timezone: correction.timezone,
+    };
+    let mut parser = parser.for_file(path);
+    if let Some(name) = parser.detect(head_text.lines(), &context) {
+        parser.prefer(name);
     }
+    let first = head_text
+        .lines()
+        .find_map(|line| parser.parse(line, &context).ok());
+    let last = tail_lines
+        .iter()
+        .rev()
+        .find_map(|line| parser.parse(line, &context).ok());
+    Ok(first
+        .zip(last)
+        .map(|(first, last)| (first + correction.skew, last + correction.skew)))
+}
+
+/// Merges the records of all `files` into one sequence ordered by timestamp.
+#[cfg(test)]
+pub fn merge_files(
+    files: &[FileWithLines],
+    parser: &TimestampParser,
+) -> Result<Vec<Line>, LineError> {
+    let file_lines: Vec<Vec<Line>> = files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| {
+            file.get_annotated_lines(i, parser)
+                .map_err(|err| LineError {
+                    error_message: format!("file={}, error={}", file.filename(), err),
+                })
+        })
+        .collect::<Result<Vec<Vec<Line>>, LineError>>()?;
+    Ok(crate::mergeline::merge_all(&file_lines))
+}
+
+/// Filename without the directory, archive members keep the name of their archive, e.g. `bundle.zip!/app/one.log`.
+pub fn short_filename(filename: &str) -> String {
+    let (archive, member) = decompress::split_member(filename);
+    let archive_name = Path::new(archive)
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+    match member {
+        Some(member) => decompress::member_path(&archive_name, member),
+        None => archive_name,
+    }
+}
+
+/// Names of files shown side by side, `short_filename` unless another file has the same one.
+/// Such files are named by their path below the directory holding all files, at most the scanned one,
+/// e.g. `host1/app/app.log` and `host2/app/app.log`.
+pub fn unique_names(filenames: &[String]) -> Vec<String> {
+    let names: Vec<String> = filenames.iter().map(|f| short_filename(f)).collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for name in &names {
+        *counts.entry(name).or_default() += 1;
+    }
+    if counts.values().all(|count| *count == 1) {
+        return names;
+    }
+
+    let dirs: Vec<&Path> = filenames
+        .iter()
+        .map(|f| {
+            let (archive, _) = decompress::split_member(f);
+            Path::new(archive).parent().unwrap_or(Path::new(""))
+        })
+        .collect();
+    let common: PathBuf = dirs[1..].iter().fold(dirs[0].to_path_buf(), |common, dir| {
+        common
+            .components()
+            .zip(dir.components())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    });
+    names
+        .iter()
+        .zip(filenames)
+        .map(|(name, filename)| {
+            if counts[name.as_str()] == 1 {
+                return name.clone();
+            }
+            let (archive, member) = decompress::split_member(filename);
+            let relative = Path::new(archive)
+                .strip_prefix(&common)
+                .unwrap_or(Path::new(archive))
+                .to_string_lossy()
+                .into_owned();
+            match member {
+                Some(member) => decompress::member_path(&relative, member),
+                None => relative,
+            }
+        })
+        .collect()
+}
+
+/// Shortens the names of files shown side by side to the part telling them apart,
+/// e.g. `NetworkSocket_t80.log` and `NetworkSocket_u80.log` become `t80` and `u80`.
+pub fn distinct_names(filenames: &[String]) -> Vec<String> {
+    let names = unique_names(filenames);
+    if names.len() < 2 {
+        return names;
+    }
+
+    let is_separator = |c: char| c == '.' || c == '_' || c == '-' || c == '/' || c == ' ';
+    let first: Vec<char> = names[0].chars().collect();
+    let mut prefix_len = first.len();
+    let mut suffix_len = first.len();
+    for name in &names[1..] {
+        let chars: Vec<char> = name.chars().collect();
+        prefix_len = min(
+            prefix_len,
+            first
+                .iter()
+                .zip(chars.iter())
+                .take_while(|(a, b)| a == b)
+                .count(),
+        );
+        suffix_len = min(
+            suffix_len,
+            first
+                .iter()
+                .rev()
+                .zip(chars.iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count(),
+        );
+    }
+    // only cut at separators, so `t80` and `u80` keep their common `80`
+    while prefix_len > 0 && !is_separator(first[prefix_len - 1]) {
+        prefix_len -= 1;
+    }
+    while suffix_len > 0 && !is_separator(first[first.len() - suffix_len]) {
+        suffix_len -= 1;
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            let chars: Vec<char> = name.chars().collect();
+            if prefix_len + suffix_len >= chars.len() {
+                return name.clone();
+            }
+            chars[prefix_len..chars.len() - suffix_len].iter().collect()
+        })
+        .collect()
 }
@@ -0,0 +1,49 @@
+//! Logfiles shared by the tests of the modules working on loaded files.
+
+use super::FileWithLines;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Two interleaved files with levels, continuation lines, a CRLF line end and a last line without a line end.
+pub fn generate_files() -> Vec<FileWithLines> {
+    vec![
+        FileWithLines::new(
+            String::from("/logs/one.log"),
+            String::from("2023-05-03 10:25:50.000000 INFO  - session=1 request\r\n2023-05-03 10:25:52.000000 ERROR - session=2 failed\n\tat Main.main(Main.java:3)\n"),
+        ),
+        FileWithLines::new(
+            String::from("/logs/two.log"),
+            String::from("2023-05-03 10:25:51.000000 WARN  - session=1 slow\nno timestamp\n2023-05-03 10:25:53.000000 - session=2 no level"),
+        ),
+    ]
+}
+
+/// A directory below the temp dir, named after the test and the process, removed with its contents when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("logchuck_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `contents` into the file at `relative`, creating the directories holding it.
+    pub fn write(&self, relative: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.0.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,15 +1,11 @@
 use super::*;
+use fixtures::TempDir;
 
 #[test]
 fn load_file_get_ith_line() {
     let text: String = String::from("We did the slice.\nIt was the spooky slice.\nNow our swings have some spice.\nSpoooooky.\nSlice.\n");
-    let line_breaks: Vec<usize> = get_line_breaks(&text);
 
-    let spooky_file = FileWithLines {
-        text,
-        line_breaks,
-        filename: String::from("spooky.txt"),
-    };
+    let spooky_file = FileWithLines::new(String::from("spooky.txt"), text);
     assert_eq!("We did the slice.", spooky_file.get_ith_line(0).unwrap());
     assert_eq!(
         "It was the spooky slice.",
@@ -27,28 +23,342 @@ fn load_file_get_ith_line() {
 #[test]
 fn annotated_lines_test() {
     let text: String = String::from("2023-05-03 10:25:50.262116 - one\n2023-05-03 10:25:50.262116 - two\n2023-05-03 10:25:50.262116 - three\n");
-    let line_breaks: Vec<usize> = get_line_breaks(&text);
-    let spooky_file = FileWithLines {
-        text,
-        line_breaks,
-        filename: String::from("spooky.txt"),
-    };
+    let spooky_file = FileWithLines::new(String::from("spooky.txt"), text);
 
     assert_eq!(spooky_file.len(), 3);
 
-    let res = spooky_file.get_annotated_lines(0);
+    assert_eq!(spooky_file.detected_format(), None);
+    let res = spooky_file.get_annotated_lines(0, &TimestampParser::default());
     assert!(res.is_ok());
     assert_eq!(res.unwrap().len(), 3);
+    assert_eq!(spooky_file.detected_format(), Some("ISO 8601"));
 }
 
 #[test]
 fn missing_newline_at_the_end() {
     let text: String = String::from("We did the slice.\nIt was the spooky slice.\nNow our swings have some spice.\nSpoooooky.\nSlice.");
-    let line_breaks: Vec<usize> = get_line_breaks(&text);
-    let spooky_file = FileWithLines {
-        text,
-        line_breaks,
-        filename: String::from("spooky.txt"),
-    };
+    let spooky_file = FileWithLines::new(String::from("spooky.txt"), text);
     assert_eq!(spooky_file.len(), 5);
 }
+
+#[test]
+fn continuation_lines_belong_to_previous_record() {
+    let text: String = String::from("preamble without timestamp\n2023-05-03 10:25:50.262116 - one\njava.lang.NullPointerException\n\tat Main.main(Main.java:3)\n2023-05-03 10:25:51.262116 - two\n");
+    let spooky_file = FileWithLines::new(String::from("spooky.txt"), text);
+
+    let lines = spooky_file
+        .get_annotated_lines(0, &TimestampParser::default())
+        .unwrap();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[1].index, 1);
+    assert_eq!(lines[1].line_count, 3);
+    assert_eq!(lines[2].index, 4);
+    assert_eq!(lines[2].line_count, 1);
+
+    // the preamble is kept as a record with the timestamp of the first one
+    assert_eq!(lines[0].index, 0);
+    assert_eq!(lines[0].line_count, 1);
+    assert_eq!(lines[0].timestamp, lines[1].timestamp);
+    assert_eq!(
+        spooky_file.get_record(&lines[0]).unwrap(),
+        "preamble without timestamp"
+    );
+
+    assert_eq!(
+        spooky_file.get_record(&lines[1]).unwrap(),
+        "2023-05-03 10:25:50.262116 - one\njava.lang.NullPointerException\n\tat Main.main(Main.java:3)"
+    );
+    assert_eq!(
+        spooky_file.get_record(&lines[2]).unwrap(),
+        "2023-05-03 10:25:51.262116 - two"
+    );
+}
+
+#[test]
+fn timestamp_in_continuation_line_starts_no_record() {
+    let spooky_file = FileWithLines::new(
+        String::from("spooky.txt"),
+        String::from("2023-05-03 10:25:50 ERROR - handshake failed\nCaused by: certificate expired at 2021-01-01 00:00:00\n2023-05-03 10:25:51 INFO - retry\n"),
+    );
+
+    let (lines, out_of_order) = spooky_file
+        .get_annotated_lines_with_progress(
+            0,
+            &TimestampParser::default(),
+            &LoadProgress::default(),
+            None,
+        )
+        .unwrap();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].line_count, 2);
+    assert_eq!(lines[1].index, 2);
+    assert_eq!(out_of_order, 0);
+}
+
+#[test]
+fn distinct_names_test() {
+    let names = distinct_names(&[
+        String::from("/logs/NetworkSocket_t80.log"),
+        String::from("/logs/NetworkSocket_u80.log"),
+        String::from("/logs/NetworkSocket_u3000.log"),
+    ]);
+    assert_eq!(names, vec!["t80", "u80", "u3000"]);
+
+    let names = distinct_names(&[String::from("/logs/server.log")]);
+    assert_eq!(names, vec!["server.log"]);
+
+    // files of the same name are told apart by their directories
+    let names = distinct_names(&[
+        String::from("/bundle/host1/app/app.log"),
+        String::from("/bundle/host2/app/app.log"),
+    ]);
+    assert_eq!(names, vec!["host1", "host2"]);
+}
+
+#[test]
+fn unique_names_test() {
+    let strings = |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+    assert_eq!(
+        unique_names(&strings(&[
+            "/bundle/host1/app.log",
+            "/bundle/host1/system.log"
+        ])),
+        strings(&["app.log", "system.log"])
+    );
+    assert_eq!(
+        unique_names(&strings(&[
+            "/bundle/host1/app/app.log",
+            "/bundle/host2/app/app.log",
+            "/bundle/host2/system.log",
+            "/bundle/host1/logs.zip!/app/one.log",
+            "/bundle/host2/logs.zip!/app/one.log",
+        ])),
+        strings(&[
+            "host1/app/app.log",
+            "host2/app/app.log",
+            "system.log",
+            "host1/logs.zip!/app/one.log",
+            "host2/logs.zip!/app/one.log",
+        ])
+    );
+}
+
+#[test]
+fn time_correction_moves_timestamps() {
+    let mut file = FileWithLines::new(
+        String::from("device.log"),
+        String::from("2023-05-03 12:25:50 - local\n2023-05-03T10:25:51Z - utc\n"),
+    );
+    file.set_time_correction(TimeCorrection {
+        timezone: parse_timezone("+02:00").unwrap(),
+        skew: 2_350_000,
+    });
+
+    let lines = file
+        .get_annotated_lines(0, &TimestampParser::default())
+        .unwrap();
+    assert_eq!(lines[0].timestamp, 1683109552350000);
+    assert_eq!(lines[1].timestamp, 1683109553350000);
+}
+
+#[test]
+fn out_of_order_records_are_sorted() {
+    let file = FileWithLines::new(
+        String::from("threads.log"),
+        String::from(
+            "2023-05-03 10:25:50.100 - a\n\
+             2023-05-03 10:25:50.300 - b\n\
+             2023-05-03 10:25:50.200 - c\n\
+             \tat Main.main(Main.java:3)\n\
+             2023-05-03 10:25:50.100 - d\n\
+             2023-05-03 10:25:50.400 - e\n",
+        ),
+    );
+
+    let (lines, out_of_order) = file
+        .get_annotated_lines_with_progress(
+            0,
+            &TimestampParser::default(),
+            &LoadProgress::default(),
+            None,
+        )
+        .unwrap();
+    assert_eq!(out_of_order, 2);
+    // sorted stably, `d` stays after `a` and `c` keeps its continuation line
+    let order: Vec<(usize, usize)> = lines.iter().map(|l| (l.index, l.line_count)).collect();
+    assert_eq!(order, vec![(0, 1), (4, 1), (2, 2), (1, 1), (5, 1)]);
+}
+
+#[test]
+fn appended_lines_are_annotated() {
+    let mut file = FileWithLines::new(
+        String::from("app.log"),
+        String::from("2023-05-03 10:25:50 - one\n2023-05-03 10:25:51 - tw"),
+    );
+    let parser = TimestampParser::default();
+    assert_eq!(file.get_annotated_lines(0, &parser).unwrap().len(), 2);
+
+    let from =
+        file.append("o\n\tcontinued\n2023-05-03 10:25:53 - four\n2023-05-03 10:25:52 - three\n");
+    assert_eq!(from, 2);
+    assert_eq!(file.len(), 5);
+    assert_eq!(file.get_ith_line(1).unwrap(), "2023-05-03 10:25:51 - two");
+    assert_eq!(file.get_ith_line(4).unwrap(), "2023-05-03 10:25:52 - three");
+
+    let (lines, continued, out_of_order) = file
+        .get_appended_lines(0, &parser, from, 1683109551000000)
+        .unwrap();
+    assert_eq!(continued, 1);
+    assert_eq!(out_of_order, 1);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].index, 4);
+    assert_eq!(lines[1].index, 3);
+}
+
+#[test]
+fn file_on_disk_with_appended_lines() {
+    let dir = TempDir::new("on_disk");
+    let path = dir.write(
+        "app.log",
+        "2023-05-03 10:25:50 - one\n\tcontinued\n2023-05-03 10:25:51 - tw",
+    );
+    let progress = LoadProgress::default();
+    let mut file =
+        FileWithLines::open(path.to_string_lossy().into_owned(), &progress, None).unwrap();
+    assert!(file.on_disk.is_some());
+    assert_eq!(progress.bytes_read.load(Ordering::Relaxed), 61);
+    assert_eq!(file.size(), 61);
+    assert_eq!(file.len(), 3);
+
+    let parser = TimestampParser::default();
+    let lines = file.get_annotated_lines(0, &parser).unwrap();
+    assert_eq!(
+        file.get_record(&lines[0]).unwrap(),
+        "2023-05-03 10:25:50 - one\n\tcontinued"
+    );
+
+    // the last line on disk continues in the appended text
+    let from = file.append("o\n2023-05-03 10:25:52 - three\n");
+    assert_eq!(from, 3);
+    assert_eq!(file.len(), 4);
+    assert_eq!(file.get_ith_line(2).unwrap(), "2023-05-03 10:25:51 - two");
+    assert_eq!(file.get_ith_line(3).unwrap(), "2023-05-03 10:25:52 - three");
+    assert!(file.get_ith_line(4).is_err());
+}
+
+#[test]
+fn file_on_disk_read_across_blocks() {
+    let dir = TempDir::new("blocks");
+    let line = "2023-05-03 10:25:50 - a line\n";
+    let count = BLOCK_SIZE / line.len() + 2;
+    let path = dir.write("app.log", line.repeat(count));
+    let file = FileWithLines::open(
+        path.to_string_lossy().into_owned(),
+        &LoadProgress::default(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(file.len(), count);
+
+    // only every `LINE_STEP`th line start is kept, the others are searched for across blocks
+    let crossing = BLOCK_SIZE / line.len();
+    assert_eq!(file.get_ith_line(crossing).unwrap(), line.trim_end());
+    assert_eq!(file.get_ith_line(count - 1).unwrap(), line.trim_end());
+    let lines = file
+        .get_annotated_lines(0, &TimestampParser::default())
+        .unwrap();
+    assert_eq!(lines.len(), count);
+    assert_eq!(lines[crossing].index, crossing);
+
+    // truncated, e.g. by copytruncate, it fails to read instead of ending the process
+    fs::write(&path, "").unwrap();
+    assert!(file.get_ith_line(1).is_err());
+    assert!(file.get_record(&lines[count - 1]).is_err());
+}
+
+#[test]
+fn decode_test() {
+    // a single invalid byte is replaced instead of failing the file
+    // and the bytes are kept as they were
+    let (text, encoding, invalid) = decode(b"2023-05-03 10:25:50 caf\xe9\n".to_vec(), UTF_8);
+    assert_eq!(text, "2023-05-03 10:25:50 caf\u{fffd}\n");
+    assert_eq!(encoding, UTF_8);
+    assert_eq!(invalid.unwrap(), b"2023-05-03 10:25:50 caf\xe9\n");
+
+    let latin1 = Encoding::for_label(b"latin1").unwrap();
+    let (text, _, invalid) = decode(b"2023-05-03 10:25:50 caf\xe9\n".to_vec(), latin1);
+    assert_eq!(text, "2023-05-03 10:25:50 café\n");
+    assert!(invalid.is_none());
+
+    // byte order marks win over the configured encoding and are dropped
+    let (text, encoding, _) = decode(b"\xff\xfe1\x002\x00\n\x00".to_vec(), latin1);
+    assert_eq!(text, "12\n");
+    assert_eq!(encoding, encoding_rs::UTF_16LE);
+    let (text, encoding, invalid) = decode(b"\xef\xbb\xbfone\n".to_vec(), UTF_8);
+    assert_eq!(text, "one\n");
+    assert_eq!(encoding, UTF_8);
+    assert!(invalid.is_none());
+}
+
+#[test]
+fn looks_binary_test() {
+    assert!(!looks_binary(b"2023-05-03 10:25:50 one\n\tcontinued\r\n"));
+    assert!(!looks_binary(b"caf\xe9 \x1b[31mred\x1b[0m\n"));
+    assert!(!looks_binary(b"\xff\xfe1\x00\n\x00"));
+    assert!(!looks_binary(b""));
+    assert!(looks_binary(b"\x7fELF\x02\x01\x01\x00\x00\x00"));
+    assert!(looks_binary(b"\x01\x02\x03\x04 abc"));
+}
+
+#[test]
+fn latin1_file_is_decoded() {
+    let dir = TempDir::new("latin1");
+    let path = dir.write(
+        "latin1.log",
+        b"2023-05-03 10:25:50 - caf\xe9\n2023-05-03 10:25:51 - na\xefve\n",
+    );
+    let latin1 = Encoding::for_label(b"latin1").unwrap();
+    let file = FileWithLines::read(
+        path.to_string_lossy().into_owned(),
+        latin1,
+        &LoadProgress::default(),
+        None,
+    )
+    .unwrap();
+
+    assert_eq!(file.encoding(), latin1);
+    assert_eq!(file.raw_size(), 55);
+    assert_eq!(file.size(), 57);
+    assert_eq!(file.get_ith_line(0).unwrap(), "2023-05-03 10:25:50 - café");
+    assert_eq!(file.get_ith_line(1).unwrap(), "2023-05-03 10:25:51 - naïve");
+}
+
+#[test]
+fn peek_span_of_plain_files() {
+    let dir = TempDir::new("peek_span");
+    let parser = TimestampParser::default();
+    // a preamble without timestamp, and more than the bytes peeked at in between
+    let mut text = String::from("starting\n2023-05-03 10:00:00.000 first\n");
+    text.extend((0..4000).map(|i| format!("2023-05-03 10:30:00.000 middle record {}\n", i)));
+    text.push_str("2023-05-03 11:00:00.000 last\n\tat Main.main(Main.java:3)\n");
+    let path = dir.write("app.log", &text).to_string_lossy().into_owned();
+    let first = parse_user_time("2023-05-03 10:00:00", None).unwrap();
+    let last = first + 3600 * 1_000_000;
+    assert_eq!(peek_span(&path, &parser).unwrap(), Some((first, last)));
+
+    let utf16: Vec<u8> = "\u{feff}2023-05-03 10:00:00.000 first\n2023-05-03 11:00:00.000 last\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let path = dir.write("utf16.log", utf16).to_string_lossy().into_owned();
+    assert_eq!(peek_span(&path, &parser).unwrap(), Some((first, last)));
+
+    // compressed files are left to the index cache
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    io::Write::write_all(&mut encoder, text.as_bytes()).unwrap();
+    let path = dir
+        .write("app.log.1.gz", encoder.finish().unwrap())
+        .to_string_lossy()
+        .into_owned();
+    assert_eq!(peek_span(&path, &parser).unwrap(), None);
+}
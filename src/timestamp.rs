@@ -1,8 +1,19 @@
 #[cfg(test)]
 mod test;
 
-use chrono::{DateTime, FixedOffset};
+use crate::cache::Fnv;
+use crate::config::{CorrectionConfig, EncodingConfig, FormatConfig};
+use crate::decompress;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset, Utc};
+use encoding_rs::{Encoding, UTF_8};
+use glob::Pattern;
+use regex::Regex;
 use std::fmt;
+use std::path::Path;
+
+/// Length of `YYYY-MM-DD`.
+const DATE_LEN: usize = 10;
 
 #[derive(Debug, Clone)]
 pub struct LineError {
@@ -15,7 +26,539 @@ impl fmt::Display for LineError {
     }
 }
 
-pub fn get_timestamp_from_line(line: &str) -> Result<DateTime<FixedOffset>, LineError> {
+/// What a recognizer may need to know about the file a line comes from.
+#[derive(Debug, Clone, Copy)]
+pub struct ParseContext {
+    /// Modification time of the file in microseconds, timestamps without a year are placed before it.
+    pub modified: i64,
+    /// Timezone of timestamps written without an offset.
+    pub timezone: FixedOffset,
+}
+
+/// Puts the timestamps of one file on the common clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeCorrection {
+    /// Timezone the file logs local time in, only applies to timestamps without an offset.
+    pub timezone: FixedOffset,
+    /// Added to every timestamp in microseconds, positive for a clock running behind.
+    pub skew: i64,
+}
+
+impl Default for TimeCorrection {
+    fn default() -> Self {
+        TimeCorrection {
+            timezone: Utc.fix(),
+            skew: 0,
+        }
+    }
+}
+
+impl fmt::Display for TimeCorrection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.timezone, format_skew(self.skew))
+    }
+}
+
+/// Recognizes one timestamp format and finds it in a line, returns microseconds since the epoch in UTC.
+pub trait TimestampFormat: Send + Sync {
+    /// Shown in the file list once a file turned out to use this format.
+    fn name(&self) -> &str;
+    fn parse(&self, line: &str, context: &ParseContext) -> Option<i64>;
+    /// Also finds timestamps quoted in messages, e.g. anywhere in the line, so it only applies to files detected to use it.
+    fn needs_detection(&self) -> bool {
+        false
+    }
+    /// Takes the year from the modification time of the file, see `ParseContext::modified`.
+    fn infers_year(&self) -> bool {
+        false
+    }
+}
+
+/// Lines from the start of a file used to detect its format.
+pub const DETECT_LINES: usize = 100;
+
+/// Tries its formats in order, the first one finding a timestamp wins.
+/// Formats from the configuration come before the built-in ones.
+pub struct TimestampParser {
+    custom: Vec<CustomFormat>,
+    formats: Vec<Box<dyn TimestampFormat>>,
+    /// Corrections from the configuration with the glob of the files they apply to.
+    corrections: Vec<(Pattern, TimeCorrection)>,
+    /// Encodings from the configuration with the glob of the files they apply to.
+    encodings: Vec<(Pattern, &'static Encoding)>,
+}
+
+impl Default for TimestampParser {
+    fn default() -> Self {
+        TimestampParser {
+            custom: Vec::new(),
+            corrections: Vec::new(),
+            encodings: Vec::new(),
+            formats: vec![
+                Box::new(Iso),
+                Box::new(Syslog),
+                Box::new(Epoch),
+                Box::new(CommonLog::new()),
+                Box::new(IsoAnywhere::new()),
+            ],
+        }
+    }
+}
+
+impl TimestampParser {
+    /// Built-in formats preceded by the user-defined `formats`.
+    pub fn with_custom(formats: &[FormatConfig]) -> Result<TimestampParser, LineError> {
+        Ok(TimestampParser {
+            custom: formats
+                .iter()
+                .map(CustomFormat::new)
+                .collect::<Result<Vec<CustomFormat>, LineError>>()?,
+            ..Default::default()
+        })
+    }
+
+    /// Adds timezones and skews for the files matching their globs.
+    pub fn with_corrections(
+        mut self,
+        corrections: &[CorrectionConfig],
+    ) -> Result<TimestampParser, LineError> {
+        for config in corrections {
+            let glob = Pattern::new(&config.files).map_err(|err| LineError {
+                error_message: format!("invalid filename glob={}, error={}", config.files, err),
+            })?;
+            let correction = TimeCorrection {
+                timezone: match &config.timezone {
+                    Some(timezone) => parse_timezone(timezone)?,
+                    None => Utc.fix(),
+                },
+                skew: seconds_to_micros(config.skew.unwrap_or_default()),
+            };
+            self.corrections.push((glob, correction));
+        }
+        Ok(self)
+    }
+
+    /// Adds text encodings for the files matching their globs.
+    pub fn with_encodings(
+        mut self,
+        encodings: &[EncodingConfig],
+    ) -> Result<TimestampParser, LineError> {
+        for config in encodings {
+            let glob = Pattern::new(&config.files).map_err(|err| LineError {
+                error_message: format!("invalid filename glob={}, error={}", config.files, err),
+            })?;
+            let encoding =
+                Encoding::for_label(config.encoding.as_bytes()).ok_or_else(|| LineError {
+                    error_message: format!("unknown encoding={}", config.encoding),
+                })?;
+            self.encodings.push((glob, encoding));
+        }
+        Ok(self)
+    }
+
+    /// The encoding of the first glob matching `filename`, UTF-8 if no glob does.
+    pub fn encoding_for(&self, filename: &str) -> &'static Encoding {
+        let name = glob_name(filename);
+        self.encodings
+            .iter()
+            .find(|(glob, _)| glob.matches(&name))
+            .map(|(_, encoding)| *encoding)
+            .unwrap_or(UTF_8)
+    }
+
+    /// The correction of the first glob matching `filename`, none if no glob does.
+    pub fn correction_for(&self, filename: &str) -> TimeCorrection {
+        let name = glob_name(filename);
+        self.corrections
+            .iter()
+            .find(|(glob, _)| glob.matches(&name))
+            .map(|(_, correction)| *correction)
+            .unwrap_or_default()
+    }
+
+    /// Formats to try on the lines of `filename`, leaving out custom formats meant for other files.
+    pub fn for_file(&self, filename: &str) -> FileParser<'_> {
+        let name = glob_name(filename);
+        let custom = self
+            .custom
+            .iter()
+            .filter(|f| f.applies_to(&name))
+            .map(|f| f as &dyn TimestampFormat);
+        FileParser {
+            formats: custom
+                .chain(self.formats.iter().map(|f| f.as_ref()))
+                .collect(),
+            detected: false,
+        }
+    }
+
+    /// Changes with the settings the timestamps of `filename` depend on, tells cached timestamps still apply.
+    /// `modified` is the modification time of the file, its year counts if a format takes the year from it.
+    pub fn fingerprint(&self, filename: &str, modified: i64) -> u64 {
+        let name = glob_name(filename);
+        let mut hasher = Fnv::default();
+        for format in self.custom.iter().filter(|f| f.applies_to(&name)) {
+            hasher.write_str(&format.pattern);
+            hasher.write_str(format.regex.as_str());
+        }
+        let correction = self.correction_for(filename);
+        hasher.write_u64(correction.timezone.local_minus_utc() as u64);
+        hasher.write_u64(correction.skew as u64);
+        // line breaks are offsets into the decoded text
+        hasher.write_str(self.encoding_for(filename).name());
+        let year = NaiveDateTime::from_timestamp_micros(modified).map(|m| m.year());
+        if let Some(year) = year.filter(|_| self.for_file(filename).infers_year()) {
+            hasher.write_u64(year as u64);
+        }
+        hasher.finish()
+    }
+}
+
+/// Globs are matched against the name of the file, archive members against their own name.
+fn glob_name(filename: &str) -> String {
+    let (archive, member) = decompress::split_member(filename);
+    Path::new(member.unwrap_or(archive))
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// The formats of a `TimestampParser` applying to one file.
+pub struct FileParser<'a> {
+    formats: Vec<&'a dyn TimestampFormat>,
+    /// The first of `formats` was detected for the file, see `prefer`.
+    detected: bool,
+}
+
+impl<'a> FileParser<'a> {
+    /// Finds the format parsing most of the first `DETECT_LINES` of `lines`, the earlier one on a tie.
+    pub fn detect<S: AsRef<str>>(
+        &self,
+        lines: impl Iterator<Item = S>,
+        context: &ParseContext,
+    ) -> Option<&'a str> {
+        let mut hits: Vec<usize> = vec![0; self.formats.len()];
+        for line in lines.take(DETECT_LINES) {
+            let mut anchored = false;
+            for (i, format) in self.formats.iter().enumerate() {
+                // formats needing detection also match most lines the anchored ones do, they only count on the rest
+                if format.needs_detection() && anchored {
+                    continue;
+                }
+                if format.parse(line.as_ref(), context).is_some() {
+                    hits[i] += 1;
+                    anchored |= !format.needs_detection();
+                }
+            }
+        }
+
+        let best = (0..hits.len()).rev().max_by_key(|i| hits[*i])?;
+        if hits[best] == 0 {
+            None
+        } else {
+            Some(self.formats[best].name())
+        }
+    }
+
+    /// Tries the format called `name`, detected for the file, before all others.
+    /// The rest are only tried on lines it cannot parse, those needing detection not at all.
+    pub fn prefer(&mut self, name: &str) {
+        if let Some(i) = self.formats.iter().position(|f| f.name() == name) {
+            let format = self.formats.remove(i);
+            self.formats.insert(0, format);
+            self.detected = true;
+        }
+    }
+
+    /// Whether any of the formats takes the year from the modification time of the file.
+    fn infers_year(&self) -> bool {
+        self.formats.iter().any(|f| f.infers_year())
+    }
+
+    pub fn parse(&self, line: &str, context: &ParseContext) -> Result<i64, LineError> {
+        // a timestamp quoted in a continuation line, e.g. `Caused by: expired at 2021-01-01 00:00:00`, starts no record
+        self.formats
+            .iter()
+            .enumerate()
+            .filter(|(i, f)| !f.needs_detection() || (self.detected && *i == 0))
+            .find_map(|(_, f)| f.parse(line, context))
+            .ok_or(LineError {
+                error_message: String::from("cannot parse timestamp"),
+            })
+    }
+}
+
+/// A strftime pattern from the configuration, e.g. `%d.%m.%Y %H:%M:%S:%3f`.
+/// Without a regex the timestamp is taken from as many words at the start of the line as the pattern has.
+struct CustomFormat {
+    pattern: String,
+    regex: Regex,
+    files: Option<Pattern>,
+    has_year: bool,
+    has_offset: bool,
+}
+
+impl CustomFormat {
+    fn new(config: &FormatConfig) -> Result<CustomFormat, LineError> {
+        if StrftimeItems::new(&config.pattern).any(|item| item == Item::Error) {
+            return Err(LineError {
+                error_message: format!("invalid timestamp pattern={}", config.pattern),
+            });
+        }
+
+        let regex = match &config.regex {
+            Some(regex) => regex.clone(),
+            None => {
+                let words = config.pattern.split_whitespace().count().max(1);
+                format!(r"^\s*\S+(?:\s+\S+){{{}}}", words - 1)
+            }
+        };
+        let regex = Regex::new(&regex).map_err(|err| LineError {
+            error_message: format!("invalid timestamp regex={}, error={}", regex, err),
+        })?;
+
+        let files = match &config.files {
+            Some(glob) => Some(Pattern::new(glob).map_err(|err| LineError {
+                error_message: format!("invalid filename glob={}, error={}", glob, err),
+            })?),
+            None => None,
+        };
+
+        Ok(CustomFormat {
+            has_year: [
+                "%Y", "%y", "%G", "%g", "%C", "%F", "%D", "%x", "%c", "%+", "%s",
+            ]
+            .iter()
+            .any(|spec| config.pattern.contains(spec)),
+            has_offset: ["%z", "%:z", "%#z", "%+"]
+                .iter()
+                .any(|spec| config.pattern.contains(spec)),
+            pattern: config.pattern.clone(),
+            regex,
+            files,
+        })
+    }
+
+    fn applies_to(&self, filename: &str) -> bool {
+        self.files
+            .as_ref()
+            .is_none_or(|glob| glob.matches(filename))
+    }
+}
+
+impl TimestampFormat for CustomFormat {
+    fn name(&self) -> &str {
+        &self.pattern
+    }
+
+    fn infers_year(&self) -> bool {
+        !self.has_year
+    }
+
+    fn parse(&self, line: &str, context: &ParseContext) -> Option<i64> {
+        let captures = self.regex.captures(line)?;
+        // the first group if the regex has one, the whole match otherwise
+        let time = captures.get(1).or(captures.get(0))?.as_str().trim();
+        if self.has_year {
+            parse_with_pattern(time, &self.pattern, self.has_offset, context)
+        } else {
+            parse_without_year(time, &self.pattern, self.has_offset, context)
+        }
+    }
+}
+
+/// Parses `time` in the timezone of the file unless `pattern` has an offset.
+fn parse_with_pattern(
+    time: &str,
+    pattern: &str,
+    has_offset: bool,
+    context: &ParseContext,
+) -> Option<i64> {
+    if has_offset {
+        DateTime::parse_from_str(time, pattern)
+            .ok()
+            .map(|t| t.timestamp_micros())
+    } else {
+        NaiveDateTime::parse_from_str(time, pattern)
+            .ok()
+            .map(|t| t.timestamp_micros() - offset_micros(&context.timezone))
+    }
+}
+
+fn offset_micros(timezone: &FixedOffset) -> i64 {
+    timezone.local_minus_utc() as i64 * 1_000_000
+}
+
+/// Takes the year from the modification time of the file, a timestamp after it is from the year before.
+fn parse_without_year(
+    time: &str,
+    pattern: &str,
+    has_offset: bool,
+    context: &ParseContext,
+) -> Option<i64> {
+    let modified = NaiveDateTime::from_timestamp_micros(context.modified)?;
+    let pattern = format!("%Y {}", pattern);
+    let parse = |year: i32| {
+        parse_with_pattern(&format!("{} {}", year, time), &pattern, has_offset, context)
+    };
+
+    let timestamp = parse(modified.year())?;
+    // a file written over new year has its December lines in the previous year
+    if timestamp > context.modified + MICROS_PER_DAY {
+        parse(modified.year() - 1)
+    } else {
+        Some(timestamp)
+    }
+}
+
+/// `2023-05-03 10:25:50.262116` or `2023-05-03T10:25:50Z` at the start of the line.
+struct Iso;
+
+impl TimestampFormat for Iso {
+    fn name(&self) -> &str {
+        "ISO 8601"
+    }
+
+    fn parse(&self, line: &str, context: &ParseContext) -> Option<i64> {
+        get_timestamp_from_line(line, context.timezone)
+            .ok()
+            .map(|t| t.timestamp_micros())
+    }
+}
+
+/// `May  3 10:25:50` at the start of the line, as written by syslog, has no year.
+struct Syslog;
+
+/// Length of `Mmm dd hh:mm:ss`.
+const SYSLOG_LEN: usize = 15;
+
+impl TimestampFormat for Syslog {
+    fn name(&self) -> &str {
+        "syslog"
+    }
+
+    fn infers_year(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, line: &str, context: &ParseContext) -> Option<i64> {
+        let time = line.get(..SYSLOG_LEN)?;
+        parse_without_year(time, "%b %e %H:%M:%S", false, context)
+    }
+}
+
+const MICROS_PER_DAY: i64 = 24 * 60 * 60 * 1_000_000;
+
+/// Seconds, milliseconds or microseconds since the epoch as the first word of the line, e.g. `1683109550.262`.
+struct Epoch;
+
+impl TimestampFormat for Epoch {
+    fn name(&self) -> &str {
+        "epoch"
+    }
+
+    fn parse(&self, line: &str, _: &ParseContext) -> Option<i64> {
+        let word = line.split_whitespace().next()?;
+        let word = word.trim_start_matches('[').trim_end_matches(']');
+        let (integer, fraction) = match word.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (word, None),
+        };
+        if !integer.bytes().all(|b| b.is_ascii_digit())
+            || !fraction.is_none_or(|f| !f.is_empty() && f.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return None;
+        }
+
+        let value: i64 = integer.parse().ok()?;
+        match (integer.len(), fraction) {
+            (10, None) => Some(value * 1_000_000),
+            (10, Some(fraction)) => {
+                let micros: String = fraction.chars().chain("000000".chars()).take(6).collect();
+                Some(value * 1_000_000 + micros.parse::<i64>().ok()?)
+            }
+            (13, None) => Some(value * 1_000),
+            (16, None) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn needs_detection(&self) -> bool {
+        true
+    }
+}
+
+/// `[03/May/2023:10:25:50 +0000]` anywhere in the line, as written by Apache and nginx.
+struct CommonLog {
+    regex: Regex,
+}
+
+impl CommonLog {
+    fn new() -> CommonLog {
+        CommonLog {
+            regex: Regex::new(
+                r"\[(\d{2}/[A-Za-z]{3}/\d{4}:\d{2}:\d{2}:\d{2}(?:\.\d+)? [+-]\d{4})\]",
+            )
+            .unwrap(),
+        }
+    }
+}
+
+impl TimestampFormat for CommonLog {
+    fn name(&self) -> &str {
+        "Apache/nginx"
+    }
+
+    fn parse(&self, line: &str, _: &ParseContext) -> Option<i64> {
+        let time = self.regex.captures(line)?.get(1)?.as_str();
+        DateTime::parse_from_str(time, "%d/%b/%Y:%H:%M:%S%.f %z")
+            .ok()
+            .map(|t| t.timestamp_micros())
+    }
+
+    fn needs_detection(&self) -> bool {
+        true
+    }
+}
+
+/// An ISO 8601 timestamp anywhere in the line, e.g. `[main] 2023-05-03 10:25:50` or `{"time":"2023-05-03T10:25:50Z"}`.
+struct IsoAnywhere {
+    regex: Regex,
+}
+
+impl IsoAnywhere {
+    fn new() -> IsoAnywhere {
+        IsoAnywhere {
+            regex: Regex::new(
+                r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+            )
+            .unwrap(),
+        }
+    }
+}
+
+impl TimestampFormat for IsoAnywhere {
+    fn name(&self) -> &str {
+        "ISO 8601 in line"
+    }
+
+    fn parse(&self, line: &str, context: &ParseContext) -> Option<i64> {
+        let time = self.regex.find(line)?.as_str();
+        parse_timestamp(time, context.timezone)
+            .ok()
+            .map(|t| t.timestamp_micros())
+    }
+
+    fn needs_detection(&self) -> bool {
+        true
+    }
+}
+
+/// Reads the timestamp at the start of `line`, `timezone` applies when it has no offset.
+pub fn get_timestamp_from_line(
+    line: &str,
+    timezone: FixedOffset,
+) -> Result<DateTime<FixedOffset>, LineError> {
     let mut chunks = line.split(' ');
     let mut timestamp: String = String::new();
 
@@ -27,6 +570,12 @@ pub fn get_timestamp_from_line(line: &str) -> Result<DateTime<FixedOffset>, Line
             })
         }
     }
+
+    // ISO 8601 keeps date and time in one chunk, e.g. `2023-05-03T10:25:50.262Z`
+    if timestamp.len() > DATE_LEN && timestamp.as_bytes()[DATE_LEN] == b'T' {
+        return parse_timestamp(timestamp.as_str(), timezone);
+    }
+
     timestamp.push(' ');
     match chunks.next() {
         Some(text) => timestamp.push_str(text),
@@ -37,23 +586,24 @@ pub fn get_timestamp_from_line(line: &str) -> Result<DateTime<FixedOffset>, Line
         }
     }
 
-    return parse_timestamp_utc(timestamp.as_str());
+    parse_timestamp(timestamp.as_str(), timezone)
 }
 
-fn parse_timestamp_utc(time: &str) -> Result<DateTime<FixedOffset>, LineError> {
-    let mut time_utc: String = time.to_string();
-    time_utc.push_str(" +0000");
-
-    if let Ok(date_time) = DateTime::parse_from_str(time_utc.as_str(), "%Y-%m-%d %H:%M:%S%.f %z") {
-        return Ok(date_time);
+/// Parses `YYYY-MM-DD HH:MM:SS[.f]`, with a space or `T` between date and time, followed by an optional
+/// `Z` or offset like `+02:00`. Timestamps without an offset are taken in `timezone`.
+fn parse_timestamp(time: &str, timezone: FixedOffset) -> Result<DateTime<FixedOffset>, LineError> {
+    let mut time_utc: String = time.replace(',', ".");
+    if time_utc.len() > DATE_LEN && time_utc.as_bytes()[DATE_LEN] == b'T' {
+        time_utc.replace_range(DATE_LEN..DATE_LEN + 1, " ");
     }
 
-    if let Ok(date_time) = DateTime::parse_from_str(time_utc.as_str(), "%Y-%m-%d %H:%M:%S%.f %z") {
-        return Ok(date_time);
+    if let Some(stripped) = time_utc.strip_suffix('Z') {
+        time_utc = format!("{}+0000", stripped);
+    } else if !has_offset(&time_utc) {
+        time_utc.push_str(&timezone.to_string());
     }
 
-    time_utc = time_utc.replace(',', ".");
-    if let Ok(date_time) = DateTime::parse_from_str(time_utc.as_str(), "%Y-%m-%d %H:%M:%S%.f %z") {
+    if let Ok(date_time) = DateTime::parse_from_str(time_utc.as_str(), "%Y-%m-%d %H:%M:%S%.f%z") {
         return Ok(date_time);
     }
 
@@ -61,3 +611,106 @@ fn parse_timestamp_utc(time: &str) -> Result<DateTime<FixedOffset>, LineError> {
         error_message: String::from("cannot parse timestamp"),
     })
 }
+
+/// Looks for `+hh:mm`, `-hhmm` and the like after the date, whose dashes would look like an offset too.
+fn has_offset(time: &str) -> bool {
+    time.len() > DATE_LEN
+        && time.as_bytes()[DATE_LEN..]
+            .iter()
+            .any(|b| *b == b'+' || *b == b'-')
+}
+
+/// Parses a time typed by the user, e.g. `14:32`, `2023-05-03 14:32:10.5` or `2023-05-03T14:32`, always in UTC.
+/// A time without a date takes the date of `reference`, given in microseconds.
+pub fn parse_user_time(input: &str, reference: Option<i64>) -> Result<i64, LineError> {
+    let input = input.trim().replace('T', " ");
+
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"] {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(&input, format) {
+            return Ok(date_time.timestamp_micros());
+        }
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+        return Ok(date.and_time(NaiveTime::MIN).timestamp_micros());
+    }
+
+    for format in ["%H:%M:%S%.f", "%H:%M"] {
+        if let Ok(time) = NaiveTime::parse_from_str(&input, format) {
+            return match reference.and_then(NaiveDateTime::from_timestamp_micros) {
+                Some(reference) => Ok(reference.date().and_time(time).timestamp_micros()),
+                None => Err(LineError {
+                    error_message: format!("time without a date, time={}", input),
+                }),
+            };
+        }
+    }
+
+    Err(LineError {
+        error_message: format!("cannot parse time={}", input),
+    })
+}
+
+/// Formats microseconds since the epoch the same way `parse_user_time` reads them.
+pub fn format_timestamp(micros: i64) -> String {
+    match NaiveDateTime::from_timestamp_micros(micros) {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S%.6f").to_string(),
+        None => micros.to_string(),
+    }
+}
+
+/// Parses a timezone like `+02:00`, `-0530` or `UTC`.
+pub fn parse_timezone(input: &str) -> Result<FixedOffset, LineError> {
+    let input = input.trim();
+    if input.is_empty() || input.eq_ignore_ascii_case("UTC") || input == "Z" {
+        return Ok(Utc.fix());
+    }
+
+    let error = || LineError {
+        error_message: format!("cannot parse timezone={}", input),
+    };
+    let (sign, rest) = match input.as_bytes()[0] {
+        b'+' => (1, &input[1..]),
+        b'-' => (-1, &input[1..]),
+        _ => return Err(error()),
+    };
+    let digits = rest.replace(':', "");
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(error());
+    }
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.as_str(), "0"),
+        4 => digits.split_at(2),
+        _ => return Err(error()),
+    };
+    let seconds = hours.parse::<i32>().map_err(|_| error())? * 3600
+        + minutes.parse::<i32>().map_err(|_| error())? * 60;
+    FixedOffset::east_opt(sign * seconds).ok_or_else(error)
+}
+
+/// Parses a skew in seconds like `2.35`, `+2.35s` or `-0.5`, returns microseconds.
+pub fn parse_skew(input: &str) -> Result<i64, LineError> {
+    let input = input.trim();
+    let seconds = input.strip_suffix('s').unwrap_or(input);
+    match seconds.trim_start_matches('+').parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() => Ok(seconds_to_micros(seconds)),
+        _ => Err(LineError {
+            error_message: format!("cannot parse skew={}", input),
+        }),
+    }
+}
+
+fn seconds_to_micros(seconds: f64) -> i64 {
+    (seconds * 1_000_000.0).round() as i64
+}
+
+/// Formats microseconds as signed seconds the way `parse_skew` reads them, e.g. `+2.350s`.
+pub fn format_skew(micros: i64) -> String {
+    let sign = if micros < 0 { '-' } else { '+' };
+    let micros = micros.unsigned_abs();
+    let millis = micros % 1_000_000 / 1_000;
+    match micros % 1_000 {
+        0 => format!("{}{}.{:03}s", sign, micros / 1_000_000, millis),
+        rest => format!("{}{}.{:03}{:03}s", sign, micros / 1_000_000, millis, rest),
+    }
+}
@@ -3,7 +3,7 @@ use super::*;
 #[test]
 fn timestamp_test() {
     let time = "2023-05-03 10:25:50.262116";
-    let parsed = parse_timestamp_utc(time);
+    let parsed = parse_timestamp(time, Utc.fix());
     assert!(parsed.is_ok());
     assert_eq!(parsed.unwrap().timestamp_micros(), 1683109550262116);
 }
@@ -11,7 +11,7 @@ fn timestamp_test() {
 #[test]
 fn timestamp_test_with_comma() {
     let time = "2023-05-03 10:25:50,262116";
-    let parsed = parse_timestamp_utc(time);
+    let parsed = parse_timestamp(time, Utc.fix());
     assert!(parsed.is_ok());
     assert_eq!(parsed.unwrap().timestamp_micros(), 1683109550262116);
 }
@@ -19,7 +19,400 @@ fn timestamp_test_with_comma() {
 #[test]
 fn get_from_line_test() {
     let line = "2023-05-03 10:25:50.262116     src\\main.rs INFO  - main - start";
-    let parsed = get_timestamp_from_line(line);
+    let parsed = get_timestamp_from_line(line, Utc.fix());
     assert!(parsed.is_ok());
     assert_eq!(parsed.unwrap().timestamp_micros(), 1683109550262116);
 }
+
+#[test]
+fn user_time_test() {
+    assert_eq!(
+        parse_user_time("2023-05-03 10:25:50.262116", None).unwrap(),
+        1683109550262116
+    );
+    assert_eq!(
+        parse_user_time("2023-05-03T10:25", None).unwrap(),
+        1683109500000000
+    );
+    assert_eq!(
+        parse_user_time("2023-05-03", None).unwrap(),
+        1683072000000000
+    );
+    assert_eq!(
+        parse_user_time(" 10:25:50.262116 ", Some(1683072000000000)).unwrap(),
+        1683109550262116
+    );
+    assert!(parse_user_time("10:25", None).is_err());
+    assert!(parse_user_time("yesterday", None).is_err());
+}
+
+#[test]
+fn iso_8601_with_zulu() {
+    let parsed = get_timestamp_from_line("2023-05-03T10:25:50.262116Z INFO - start", Utc.fix());
+    assert_eq!(parsed.unwrap().timestamp_micros(), 1683109550262116);
+
+    let parsed = get_timestamp_from_line("2023-05-03T10:25:50,262Z INFO - start", Utc.fix());
+    assert_eq!(parsed.unwrap().timestamp_micros(), 1683109550262000);
+}
+
+#[test]
+fn iso_8601_with_offset_is_normalized_to_utc() {
+    let parsed = get_timestamp_from_line("2023-05-03T12:25:50+02:00 INFO - start", Utc.fix());
+    assert_eq!(parsed.unwrap().timestamp_micros(), 1683109550000000);
+
+    let parsed = get_timestamp_from_line("2023-05-03T05:25:50.262116-0500 INFO - start", Utc.fix());
+    assert_eq!(parsed.unwrap().timestamp_micros(), 1683109550262116);
+
+    let parsed =
+        get_timestamp_from_line("2023-05-03 12:25:50.262116+02:00 INFO - start", Utc.fix());
+    assert_eq!(parsed.unwrap().timestamp_micros(), 1683109550262116);
+}
+
+#[test]
+fn iso_8601_without_offset_is_utc() {
+    let parsed = get_timestamp_from_line("2023-05-03T10:25:50.262116 INFO - start", Utc.fix());
+    assert_eq!(parsed.unwrap().timestamp_micros(), 1683109550262116);
+}
+
+#[test]
+fn not_a_timestamp() {
+    assert!(get_timestamp_from_line("Thread-1 started", Utc.fix()).is_err());
+    assert!(get_timestamp_from_line("\tat Main.main(Main.java:3)", Utc.fix()).is_err());
+    assert!(get_timestamp_from_line("2023-05-03T10:25:50+02:00abc", Utc.fix()).is_err());
+}
+
+#[test]
+fn non_ascii_line() {
+    assert!(
+        get_timestamp_from_line("Überprüfung der Verbindung fehlgeschlagen", Utc.fix()).is_err()
+    );
+    assert!(get_timestamp_from_line("ÄÄÄÄÄÄÄÄÄÄÄÄ", Utc.fix()).is_err());
+}
+
+fn context(modified: &str) -> ParseContext {
+    ParseContext {
+        modified: parse_user_time(modified, None).unwrap(),
+        timezone: Utc.fix(),
+    }
+}
+
+#[test]
+fn syslog_takes_year_from_modification_time() {
+    let parser = TimestampParser::default();
+    let parser = parser.for_file("app.log");
+    let parsed = parser.parse(
+        "May  3 10:25:50 host sshd[42]: Accepted publickey",
+        &context("2023-06-01 00:00"),
+    );
+    assert_eq!(parsed.unwrap(), 1683109550000000);
+
+    let parsed = parser.parse("May 13 10:25:50 host cron[1]: job", &context("2023-06-01"));
+    assert_eq!(
+        parsed.unwrap(),
+        parse_user_time("2023-05-13 10:25:50", None).unwrap()
+    );
+}
+
+#[test]
+fn syslog_over_new_year() {
+    let parser = TimestampParser::default();
+    let parser = parser.for_file("app.log");
+    let parsed = parser.parse(
+        "Dec 31 23:59:59 host app: bye",
+        &context("2024-01-01 00:10"),
+    );
+    assert_eq!(
+        parsed.unwrap(),
+        parse_user_time("2023-12-31 23:59:59", None).unwrap()
+    );
+}
+
+#[test]
+fn common_log_later_in_line() {
+    let parser = TimestampParser::default();
+    let mut parser = parser.for_file("app.log");
+    let line = r#"127.0.0.1 - - [03/May/2023:12:25:50 +0200] "GET / HTTP/1.1" 200 612"#;
+    assert!(parser.parse(line, &context("2023-06-01")).is_err());
+
+    parser.prefer("Apache/nginx");
+    let parsed = parser.parse(line, &context("2023-06-01"));
+    assert_eq!(parsed.unwrap(), 1683109550000000);
+}
+
+#[test]
+fn epoch_numbers() {
+    let parser = TimestampParser::default();
+    let mut parser = parser.for_file("app.log");
+    let context = context("2023-06-01");
+    assert!(parser.parse("1683109550 started", &context).is_err());
+
+    parser.prefer("epoch");
+    assert_eq!(
+        parser.parse("1683109550 started", &context).unwrap(),
+        1683109550000000
+    );
+    assert_eq!(
+        parser.parse("1683109550.262 started", &context).unwrap(),
+        1683109550262000
+    );
+    assert_eq!(
+        parser.parse("[1683109550262] started", &context).unwrap(),
+        1683109550262000
+    );
+    assert_eq!(
+        parser.parse("1683109550262116 started", &context).unwrap(),
+        1683109550262116
+    );
+    assert!(parser.parse("42 started", &context).is_err());
+    assert!(parser.parse("1683109550x started", &context).is_err());
+}
+
+#[test]
+fn iso_later_in_line() {
+    let parser = TimestampParser::default();
+    let mut parser = parser.for_file("app.log");
+    parser.prefer("ISO 8601 in line");
+    let parsed = parser.parse(
+        r#"{"level":"info","time":"2023-05-03T12:25:50.262116+02:00","msg":"start"}"#,
+        &context("2023-06-01"),
+    );
+    assert_eq!(parsed.unwrap(), 1683109550262116);
+
+    let parsed = parser.parse("[main] 2023-05-03 10:25:50 start", &context("2023-06-01"));
+    assert_eq!(parsed.unwrap(), 1683109550000000);
+}
+
+#[test]
+fn continuation_lines_have_no_timestamp() {
+    let parser = TimestampParser::default();
+    let parser = parser.for_file("app.log");
+    let context = context("2023-06-01");
+    assert!(parser
+        .parse("\tat Main.main(Main.java:3)", &context)
+        .is_err());
+    assert!(parser
+        .parse("java.lang.NullPointerException", &context)
+        .is_err());
+}
+
+fn custom_format(pattern: &str, regex: Option<&str>, files: Option<&str>) -> FormatConfig {
+    FormatConfig {
+        pattern: String::from(pattern),
+        regex: regex.map(String::from),
+        files: files.map(String::from),
+    }
+}
+
+#[test]
+fn custom_format_at_start_of_line() {
+    let parser =
+        TimestampParser::with_custom(&[custom_format("%d.%m.%Y %H:%M:%S:%3f", None, None)])
+            .unwrap();
+    let parsed = parser.for_file("device.log").parse(
+        "03.05.2023 10:25:50:262 sensor=4 online",
+        &context("2023-06-01"),
+    );
+    assert_eq!(parsed.unwrap(), 1683109550262000);
+}
+
+#[test]
+fn custom_format_with_regex() {
+    let parser = TimestampParser::with_custom(&[custom_format(
+        "%d.%m.%Y %H:%M:%S %z",
+        Some(r"time=<([^>]+)>"),
+        None,
+    )])
+    .unwrap();
+    let parsed = parser.for_file("device.log").parse(
+        "sensor=4 time=<03.05.2023 12:25:50 +0200> online",
+        &context("2023-06-01"),
+    );
+    assert_eq!(parsed.unwrap(), 1683109550000000);
+}
+
+#[test]
+fn custom_format_without_year() {
+    let parser =
+        TimestampParser::with_custom(&[custom_format("%d/%m %H:%M:%S", None, None)]).unwrap();
+    let parsed = parser
+        .for_file("device.log")
+        .parse("03/05 10:25:50 sensor=4 online", &context("2023-06-01"));
+    assert_eq!(parsed.unwrap(), 1683109550000000);
+}
+
+#[test]
+fn custom_format_only_for_matching_files() {
+    let parser = TimestampParser::with_custom(&[custom_format(
+        "%d.%m.%Y %H:%M:%S:%3f",
+        None,
+        Some("device*.log"),
+    )])
+    .unwrap();
+    let line = "03.05.2023 10:25:50:262 sensor=4 online";
+    let context = context("2023-06-01");
+    assert!(parser
+        .for_file("/logs/device_1.log")
+        .parse(line, &context)
+        .is_ok());
+    assert!(parser
+        .for_file("/logs/bundle.zip!/device_1.log")
+        .parse(line, &context)
+        .is_ok());
+    assert!(parser
+        .for_file("/logs/server.log")
+        .parse(line, &context)
+        .is_err());
+
+    // the built-ins still apply to the matching files
+    assert!(parser
+        .for_file("/logs/device_1.log")
+        .parse("2023-05-03 10:25:50 start", &context)
+        .is_ok());
+}
+
+#[test]
+fn invalid_custom_formats() {
+    assert!(TimestampParser::with_custom(&[custom_format("%Q", None, None)]).is_err());
+    assert!(TimestampParser::with_custom(&[custom_format("%H:%M", Some("("), None)]).is_err());
+    assert!(TimestampParser::with_custom(&[custom_format("%H:%M", None, Some("[a"))]).is_err());
+}
+
+#[test]
+fn detect_most_common_format() {
+    let parser = TimestampParser::default();
+    let mut parser = parser.for_file("app.log");
+    let context = context("2023-06-01");
+    let lines = [
+        "[main] 2023-05-03 10:25:50 start",
+        "[main] 2023-05-03 10:25:51 listening",
+        "1683109550 2023-05-03 10:25:52 request id in front",
+        "\tat Main.main(Main.java:3)",
+    ];
+
+    // without detection neither the epoch in front nor the timestamp in the line count
+    assert!(parser.parse(lines[2], &context).is_err());
+
+    let detected = parser.detect(lines.iter().copied(), &context);
+    assert_eq!(detected, Some("ISO 8601 in line"));
+    parser.prefer(detected.unwrap());
+    assert_eq!(
+        parser.parse(lines[2], &context).unwrap(),
+        parse_user_time("2023-05-03 10:25:52", None).unwrap()
+    );
+    assert!(parser.parse(lines[3], &context).is_err());
+}
+
+#[test]
+fn detect_prefers_earlier_format_on_tie() {
+    let parser = TimestampParser::default();
+    let parser = parser.for_file("app.log");
+    let lines = ["2023-05-03 10:25:50 start", "2023-05-03 10:25:51 stop"];
+    assert_eq!(
+        parser.detect(lines.iter().copied(), &context("2023-06-01")),
+        Some("ISO 8601")
+    );
+    assert_eq!(
+        parser.detect(["no", "timestamps"].iter().copied(), &context("2023-06-01")),
+        None
+    );
+}
+
+#[test]
+fn timezone_only_for_timestamps_without_offset() {
+    let parser = TimestampParser::default();
+    let mut parser = parser.for_file("device.log");
+    let context = ParseContext {
+        timezone: parse_timezone("+02:00").unwrap(),
+        ..context("2023-06-01")
+    };
+    assert_eq!(
+        parser.parse("2023-05-03 12:25:50 local", &context).unwrap(),
+        1683109550000000
+    );
+    assert_eq!(
+        parser
+            .parse("May  3 12:25:50 host app: local", &context)
+            .unwrap(),
+        1683109550000000
+    );
+    assert_eq!(
+        parser.parse("2023-05-03T10:25:50Z utc", &context).unwrap(),
+        1683109550000000
+    );
+    parser.prefer("epoch");
+    assert_eq!(
+        parser.parse("1683109550 epoch", &context).unwrap(),
+        1683109550000000
+    );
+}
+
+#[test]
+fn parse_timezone_test() {
+    assert_eq!(parse_timezone("UTC").unwrap(), Utc.fix());
+    assert_eq!(parse_timezone("").unwrap(), Utc.fix());
+    assert_eq!(parse_timezone("+02:00").unwrap().local_minus_utc(), 7200);
+    assert_eq!(parse_timezone("-0530").unwrap().local_minus_utc(), -19800);
+    assert_eq!(parse_timezone("+1").unwrap().local_minus_utc(), 3600);
+    assert!(parse_timezone("Europe/Prague").is_err());
+    assert!(parse_timezone("+123").is_err());
+    assert!(parse_timezone("+99:00").is_err());
+}
+
+#[test]
+fn skew_round_trip() {
+    assert_eq!(parse_skew("2.35").unwrap(), 2_350_000);
+    assert_eq!(parse_skew("+2.35s").unwrap(), 2_350_000);
+    assert_eq!(parse_skew("-0.5").unwrap(), -500_000);
+    assert!(parse_skew("soon").is_err());
+    assert_eq!(format_skew(2_350_000), "+2.350s");
+    assert_eq!(format_skew(-500_000), "-0.500s");
+    assert_eq!(format_skew(1_000_001), "+1.000001s");
+    assert_eq!(format_skew(0), "+0.000s");
+    for micros in [2_350_000, -500_000, 1_000_001, -42] {
+        assert_eq!(parse_skew(&format_skew(micros)).unwrap(), micros);
+    }
+}
+
+#[test]
+fn corrections_by_glob() {
+    let parser = TimestampParser::default()
+        .with_corrections(&[CorrectionConfig {
+            files: String::from("device*.log"),
+            timezone: Some(String::from("+02:00")),
+            skew: Some(2.35),
+        }])
+        .unwrap();
+    let correction = parser.correction_for("/logs/device_1.log");
+    assert_eq!(correction.timezone.local_minus_utc(), 7200);
+    assert_eq!(correction.skew, 2_350_000);
+    assert_eq!(
+        parser.correction_for("/logs/server.log"),
+        TimeCorrection::default()
+    );
+}
+
+#[test]
+fn encodings_by_glob() {
+    let parser = TimestampParser::default()
+        .with_encodings(&[EncodingConfig {
+            files: String::from("device*.log"),
+            encoding: String::from("latin1"),
+        }])
+        .unwrap();
+    assert_eq!(
+        parser.encoding_for("/logs/device_1.log").name(),
+        "windows-1252"
+    );
+    assert_eq!(parser.encoding_for("/logs/server.log"), UTF_8);
+    assert_ne!(
+        parser.fingerprint("/logs/device_1.log", 0),
+        TimestampParser::default().fingerprint("/logs/device_1.log", 0)
+    );
+
+    assert!(TimestampParser::default()
+        .with_encodings(&[EncodingConfig {
+            files: String::from("*.log"),
+            encoding: String::from("klingon"),
+        }])
+        .is_err());
+}